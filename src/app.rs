@@ -1,17 +1,42 @@
 use std::f64::consts::{PI, TAU};
 
-use crate::pendulum::{DoublePendulum, Pendulum};
-use eframe::{
-    egui::{self, util::History},
-    emath,
+use std::fmt::Write;
+use std::sync::mpsc::{Receiver, Sender};
+
+use crate::files::{self, OpenedFile};
+use crate::flip_map::FlipMap;
+#[cfg(target_arch = "wasm32")]
+use crate::link;
+use crate::poincare_plot::PoincarePlot;
+use crate::spectrum_view::SpectrumView;
+use double_pendulum::{
+    history::History,
+    integrator::Method,
+    lyapunov::LyapunovEstimator,
+    pendulum::{NPendulum, Pendulum, State},
+    preset::PRESETS,
+    scenario::{Format, Scenario, SimulationSettings},
+    timeline::Timeline,
+    trajectory::round_time,
 };
+use eframe::{egui, emath};
 
-const MASS_COEFFICIENT: f32 = 1.0;
+const SCENARIO_FILTER: files::Filter = ("Scenario", &["toml", "json"]);
+const CSV_FILTER: files::Filter = ("CSV", &["csv"]);
+
+/// Snapshots kept for rewinding, a little over three minutes at the default time step.
+const TIMELINE_CAPACITY: usize = 40_000;
+
+/// Seconds of angles, velocities, accelerations and energies kept for the plots, and so for the
+/// CSV export.
+const PLOT_HISTORY: f32 = 10.0;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Tab {
     Pendulum,
     Plots,
+    Spectrum,
+    FlipMap,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -20,56 +45,126 @@ enum Plot {
     Velocity,
     Acceleration,
     Position,
+    Phase,
+    Configuration,
+    Energy,
+    Spread,
+    Lyapunov,
+    Poincare,
 }
 
 pub struct App {
-    dp: DoublePendulum,
+    dp: NPendulum,
 
     time_step: f32,
+    simulation_speed: f32,
+    max_substeps: u32,
+    accumulator: f32,
 
     running: bool,
-    moving: (bool, bool),
-
-    canvas_size: egui::Vec2,
+    /// Index of the link being dragged by the pointer.
+    moving: Option<usize>,
+    /// Whether letting go of a dragged bob gives it the pointer's velocity, rather than
+    /// releasing it from rest.
+    throw_on_release: bool,
+
+    pixels_per_metre: f32,
+    bob_radius: f32,
+    /// Maps world coordinates (metres, origin at the pivot, y down) onto the canvas.
     canvas_transform: emath::RectTransform,
 
-    #[cfg(not(target_arch = "wasm32"))]
-    epoch: std::time::Instant,
-
-    position_history: History<(egui::Pos2, egui::Pos2)>,
-    angle_history: History<(f32, f32)>,
-    velocity_history: History<(f32, f32)>,
-    acceleration_history: History<(f32, f32)>,
+    /// Simulated time since the run started, in seconds. Histories are keyed by it.
+    time: f64,
+    /// Snapshots of `dp` after every step, for rewinding.
+    timeline: Timeline,
+
+    position_history: History<Vec<egui::Pos2>>,
+    angle_history: History<Vec<f32>>,
+    velocity_history: History<Vec<f32>>,
+    acceleration_history: History<Vec<f32>>,
+    energy_history: History<(f32, f32)>,
+    energy_drift_history: History<f32>,
+    reference_energy: Option<f32>,
+
+    /// Copies of `dp` started from slightly perturbed angles, simulated alongside it.
+    ensemble: Vec<NPendulum>,
+    ensemble_size: usize,
+    /// Largest initial angle offset within the ensemble, in radians.
+    perturbation: f32,
+    /// RMS distance of the ensemble's last bobs from their centroid.
+    spread_history: History<f32>,
+
+    lyapunov: LyapunovEstimator,
+    /// Running estimates of the largest Lyapunov exponent.
+    lyapunov_history: History<f32>,
+
+    poincare: PoincarePlot,
+    spectrum: SpectrumView,
 
     current_tab: Tab,
     current_plot: Plot,
+    /// Link whose phase portrait is shown.
+    phase_link: usize,
+    flip_map: FlipMap,
+
+    scenario_sender: Sender<Result<OpenedFile, String>>,
+    scenario_receiver: Receiver<Result<OpenedFile, String>>,
+    /// Outcome of the last file operation, shown under the scenario buttons.
+    status: Option<Result<String, String>>,
 
     first_frame: bool,
 }
 
 impl Default for App {
     fn default() -> Self {
+        let (scenario_sender, scenario_receiver) = std::sync::mpsc::channel();
+
         Self {
-            dp: DoublePendulum::default(),
+            dp: NPendulum::default(),
 
-            time_step: 1.0 / 6.0,
+            time_step: 0.005,
+            simulation_speed: 1.0,
+            max_substeps: 64,
+            accumulator: 0.0,
 
             running: true,
-            moving: (false, false),
+            moving: None,
+            throw_on_release: true,
 
-            canvas_size: egui::Vec2::ZERO,
+            pixels_per_metre: 150.0,
+            bob_radius: 15.0,
             canvas_transform: emath::RectTransform::identity(egui::Rect::ZERO),
 
-            #[cfg(not(target_arch = "wasm32"))]
-            epoch: std::time::Instant::now(),
+            time: 0.0,
+            timeline: Timeline::new(TIMELINE_CAPACITY),
 
             position_history: History::new(0..10000, 5.0 * 3600.0),
-            angle_history: History::new(1..10000, 10.0),
-            velocity_history: History::new(1..10000, 10.0),
-            acceleration_history: History::new(1..10000, 10.0),
+            angle_history: History::new(1..10000, PLOT_HISTORY),
+            velocity_history: History::new(1..10000, PLOT_HISTORY),
+            acceleration_history: History::new(1..10000, PLOT_HISTORY),
+            energy_history: History::new(1..10000, PLOT_HISTORY),
+            energy_drift_history: History::new(1..10000, PLOT_HISTORY),
+            reference_energy: None,
+
+            ensemble: Vec::new(),
+            ensemble_size: 0,
+            perturbation: 1e-4,
+            spread_history: History::new(1..10000, 60.0),
+
+            lyapunov: LyapunovEstimator::new(&NPendulum::default()),
+            lyapunov_history: History::new(1..10000, 600.0),
+
+            poincare: PoincarePlot::default(),
+            spectrum: SpectrumView::default(),
 
             current_tab: Tab::Pendulum,
             current_plot: Plot::Angle,
+            phase_link: 0,
+            flip_map: FlipMap::default(),
+
+            scenario_sender,
+            scenario_receiver,
+            status: None,
 
             first_frame: true,
         }
@@ -79,14 +174,18 @@ impl Default for App {
 impl eframe::App for App {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         self.input(ctx);
+        self.receive_scenarios();
         self.move_pendula(ctx);
 
-        self.dp.pendula.0.pivot = 0.5 * self.canvas_size.to_pos2();
-        self.dp.pendula.1.pivot = self.dp.pendula.0.position();
+        self.dp.update_pivots();
 
         if self.running & !self.moving() {
-            self.dp.update(self.time_step);
+            self.advance(ctx.input(|i| i.unstable_dt));
+        } else {
+            self.accumulator = 0.0;
         }
+        self.poincare
+            .advance_batch(ctx.input(|i| i.unstable_dt), self.time_step);
 
         if !self.first_frame {
             self.record_history();
@@ -100,8 +199,277 @@ impl eframe::App for App {
 }
 
 impl App {
+    /// Advances the simulation by the physical time corresponding to `frame_time` seconds of wall
+    /// clock, in whole steps of `time_step`. Any backlog beyond `max_substeps` steps is dropped
+    /// so that a slow frame cannot make the next one even slower.
+    fn advance(&mut self, frame_time: f32) {
+        self.accumulator += self.simulation_speed * frame_time;
+
+        let mut substeps = 0;
+        while self.running && self.accumulator >= self.time_step && substeps < self.max_substeps {
+            self.step();
+            self.accumulator -= self.time_step;
+            substeps += 1;
+        }
+
+        if substeps == self.max_substeps {
+            self.accumulator = self.accumulator.min(self.time_step);
+        }
+    }
+
+    /// Advances the pendulum and everything simulated alongside it by one `time_step`, and
+    /// records the result on the timeline.
+    fn step(&mut self) {
+        if self.timeline.is_empty() {
+            self.timeline.record(self.time, &self.dp);
+        }
+
+        let before = self.dp.state();
+        if let Err(e) = self.dp.update(self.time_step) {
+            // Stay at the last finite state, from where the run can be rewound or adjusted.
+            self.running = false;
+            self.accumulator = 0.0;
+            self.status = Some(Err(format!("Paused at {:.3} s: {e}", self.time)));
+            return;
+        }
+        self.time += f64::from(self.time_step);
+        for pendulum in &mut self.ensemble {
+            pendulum.copy_parameters(&self.dp);
+            // A member that blows up stays where it was; the next restart replaces it.
+            pendulum.update(self.time_step).ok();
+        }
+        self.lyapunov.step(&self.dp, self.time_step);
+        self.poincare.record(&before, &self.dp);
+        self.spectrum.record(self.time, &self.dp);
+
+        self.timeline.record(self.time, &self.dp);
+    }
+
+    /// Goes back, or forward, to the snapshot at `time` and pauses there. Running on from an
+    /// earlier moment forks the run, overwriting what came after it.
+    fn rewind_to(&mut self, time: f64) {
+        let Some((time, pendulum)) = self.timeline.at(time) else {
+            return;
+        };
+        self.dp = pendulum.clone();
+        self.time = time;
+        self.accumulator = 0.0;
+        self.running = false;
+
+        self.position_history.truncate_after(time);
+        self.angle_history.truncate_after(time);
+        self.velocity_history.truncate_after(time);
+        self.acceleration_history.truncate_after(time);
+        self.energy_history.truncate_after(time);
+        self.energy_drift_history.truncate_after(time);
+        self.restart_analyses();
+    }
+
+    fn step_back(&mut self) {
+        if let Some((time, _)) = self.timeline.before(self.time) {
+            self.rewind_to(time);
+        }
+    }
+
+    /// Steps forward through the recorded snapshots, simulating once past the last one.
+    fn step_forward(&mut self) {
+        match self.timeline.after(self.time) {
+            Some((time, _)) => self.rewind_to(time),
+            None => {
+                self.running = false;
+                self.step();
+            }
+        }
+    }
+
+    fn scenario(&self) -> Scenario {
+        Scenario::new(
+            &self.dp,
+            SimulationSettings {
+                time_step: self.time_step,
+                speed: self.simulation_speed,
+                max_substeps: self.max_substeps,
+            },
+        )
+    }
+
+    fn load_scenario(&mut self, scenario: &Scenario) {
+        self.dp = scenario.pendulum();
+        self.time_step = scenario.simulation.time_step;
+        self.simulation_speed = scenario.simulation.speed;
+        self.max_substeps = scenario.simulation.max_substeps;
+        self.accumulator = 0.0;
+        self.restart_run();
+    }
+
+    fn save_scenario(&mut self) {
+        let scenario = self.scenario();
+        let result = files::save("scenario.toml", SCENARIO_FILTER, |name| {
+            let format = Format::from_file_name(name).unwrap_or(Format::Toml);
+            scenario.to_string(format).map_err(|e| e.to_string())
+        });
+
+        self.status = result.err().map(Err);
+    }
+
+    fn export_csv(&mut self) {
+        let csv = self.history_csv();
+        let result = files::save("pendulum.csv", CSV_FILTER, |_| csv);
+        self.status = result.err().map(Err);
+    }
+
+    /// The recorded time series as CSV: one row per sample and, for every link, its angle,
+    /// velocity, acceleration and bob position. Comment lines at the top describe the run and
+    /// the stretch of it the rows cover, which is only the last [`PLOT_HISTORY`] seconds.
+    fn history_csv(&self) -> Result<String, String> {
+        let mut csv = String::new();
+
+        let scenario = self
+            .scenario()
+            .to_string(Format::Toml)
+            .map_err(|e| e.to_string())?;
+        for line in scenario.lines().filter(|line| !line.is_empty()) {
+            writeln!(csv, "# {line}").ok();
+        }
+        for (i, pendulum) in self.dp.pendula.iter().enumerate() {
+            writeln!(
+                csv,
+                "# link {}: {} flips, {:+} turns",
+                i + 1,
+                pendulum.flips,
+                pendulum.turns
+            )
+            .ok();
+        }
+        writeln!(
+            csv,
+            "# units: s, rad, rad/s, rad/s^2, m (y down from the pivot)"
+        )
+        .ok();
+
+        // All histories are recorded together, but keep different amounts of the past.
+        let samples = self
+            .angle_history
+            .len()
+            .min(self.velocity_history.len())
+            .min(self.acceleration_history.len())
+            .min(self.position_history.len());
+        let tail = |len: usize| len - samples;
+
+        let mut times = self
+            .angle_history
+            .iter()
+            .skip(tail(self.angle_history.len()))
+            .map(|(time, _)| round_time(time));
+        if let Some(first) = times.next() {
+            writeln!(
+                csv,
+                "# window: t = {first} to {} s; only the last {PLOT_HISTORY} s of a run are kept",
+                times.last().unwrap_or(first)
+            )
+            .ok();
+        }
+
+        csv.push_str("time");
+        for link in 1..=self.dp.links() {
+            write!(
+                csv,
+                ",angle_{link},velocity_{link},acceleration_{link},x_{link},y_{link}"
+            )
+            .ok();
+        }
+        csv.push('\n');
+
+        let rows = self
+            .angle_history
+            .iter()
+            .skip(tail(self.angle_history.len()))
+            .zip(
+                self.velocity_history
+                    .iter()
+                    .skip(tail(self.velocity_history.len())),
+            )
+            .zip(
+                self.acceleration_history
+                    .iter()
+                    .skip(tail(self.acceleration_history.len())),
+            )
+            .zip(
+                self.position_history
+                    .iter()
+                    .skip(tail(self.position_history.len())),
+            );
+
+        let cell = |value: Option<&f32>| value.map(f32::to_string).unwrap_or_default();
+        for ((((time, angles), (_, velocities)), (_, accelerations)), (_, positions)) in rows {
+            write!(csv, "{}", round_time(time)).ok();
+            for link in 0..self.dp.links() {
+                let position = positions.get(link);
+                write!(
+                    csv,
+                    ",{},{},{},{},{}",
+                    cell(angles.get(link)),
+                    cell(velocities.get(link)),
+                    cell(accelerations.get(link)),
+                    cell(position.map(|p| &p.x)),
+                    cell(position.map(|p| &p.y)),
+                )
+                .ok();
+            }
+            csv.push('\n');
+        }
+
+        Ok(csv)
+    }
+
+    /// Starts from the scenario in the page URL, if there is one.
+    #[cfg(target_arch = "wasm32")]
+    pub fn from_page_url() -> Self {
+        let mut app = Self::default();
+        if let Some(fragment) = link::fragment() {
+            let scenario = Scenario::parse_link_fragment(&fragment).map_err(|e| e.to_string());
+            app.status = Some(scenario.map(|scenario| {
+                app.load_scenario(&scenario);
+                "Loaded scenario from link".to_string()
+            }));
+        }
+        app
+    }
+
+    /// Puts the current scenario in the page URL and copies the link.
+    #[cfg(target_arch = "wasm32")]
+    fn copy_link(&mut self, ctx: &egui::Context) {
+        let link = self
+            .scenario()
+            .to_link_fragment()
+            .map_err(|e| e.to_string())
+            .and_then(|fragment| link::set_fragment(&fragment));
+
+        self.status = Some(link.map(|link| {
+            ctx.output_mut(|output| output.copied_text = link);
+            "Link copied".to_string()
+        }));
+    }
+
+    fn receive_scenarios(&mut self) {
+        while let Ok(file) = self.scenario_receiver.try_recv() {
+            let scenario = file.and_then(|file| {
+                Scenario::parse_file(&file.name, &file.contents)
+                    .map(|scenario| (file.name, scenario))
+                    .map_err(|e| e.to_string())
+            });
+
+            self.status = Some(scenario.map(|(name, scenario)| {
+                self.load_scenario(&scenario);
+                format!("Loaded {name}")
+            }));
+        }
+    }
+
     fn input(&mut self, ctx: &egui::Context) {
-        ctx.input_mut(|i| {
+        // Arrow keys belong to the focused widget, if any.
+        let stepping = ctx.memory(|m| m.focus().is_none());
+        let (back, forward) = ctx.input_mut(|i| {
             if i.key_pressed(egui::Key::Space) {
                 self.running = !self.running;
             }
@@ -109,7 +477,19 @@ impl App {
             if i.consume_key(egui::Modifiers::CTRL | egui::Modifiers::ALT, egui::Key::R) {
                 self.reset();
             }
+
+            (
+                stepping && i.key_pressed(egui::Key::ArrowLeft),
+                stepping && i.key_pressed(egui::Key::ArrowRight),
+            )
         });
+
+        if back {
+            self.step_back();
+        }
+        if forward {
+            self.step_forward();
+        }
     }
 
     fn ui(&mut self, ctx: &egui::Context) {
@@ -117,8 +497,13 @@ impl App {
         self.settings(ctx);
 
         match self.current_tab {
-            Tab::Pendulum => self.canvas(ctx),
+            Tab::Pendulum => {
+                self.timeline_bar(ctx);
+                self.canvas(ctx);
+            }
             Tab::Plots => self.plots(ctx),
+            Tab::Spectrum => self.spectrum(ctx),
+            Tab::FlipMap => self.flip_map(ctx),
         }
     }
 
@@ -135,6 +520,16 @@ impl App {
                     Tab::Plots,
                     egui::RichText::new("Plots").heading(),
                 );
+                ui.selectable_value(
+                    &mut self.current_tab,
+                    Tab::Spectrum,
+                    egui::RichText::new("Spectrum").heading(),
+                );
+                ui.selectable_value(
+                    &mut self.current_tab,
+                    Tab::FlipMap,
+                    egui::RichText::new("Flip map").heading(),
+                );
             })
         });
     }
@@ -156,71 +551,268 @@ impl App {
                             .show(ui, |ui| {
                                 ui.label("Gravity:");
                                 ui.add(
-                                    egui::Slider::new(&mut self.dp.gravity, 0.1..=15.0)
-                                        .fixed_decimals(2),
+                                    egui::Slider::new(&mut self.dp.gravity, 0.1..=25.0)
+                                        .fixed_decimals(2)
+                                        .suffix(" m/s²"),
                                 );
                                 ui.end_row();
 
-                                ui.label("Damping:");
+                                ui.label("Air drag:");
                                 ui.add(
-                                    egui::Slider::new(&mut self.dp.damping, 0.0..=0.01)
+                                    egui::Slider::new(&mut self.dp.air_drag, 0.0..=1.0)
                                         .fixed_decimals(3)
-                                        .step_by(0.001),
+                                        .suffix(" kg/m"),
                                 );
                                 ui.end_row();
 
                                 ui.label("Time step:");
                                 ui.add(
-                                    egui::Slider::new(&mut self.time_step, 0.01..=0.6)
-                                        .fixed_decimals(2),
+                                    egui::Slider::new(&mut self.time_step, 0.0005..=0.05)
+                                        .logarithmic(true)
+                                        .fixed_decimals(4)
+                                        .suffix(" s"),
                                 );
                                 ui.end_row();
-                            });
-                        ui.separator();
 
-                        ui.heading("First pendulum");
-                        egui::Grid::new("first_pendulum_grid")
-                            .striped(true)
-                            .spacing([20.0, 5.0])
-                            .show(ui, |ui| {
-                                ui.label("Mass:");
+                                ui.label("Speed:");
+                                ui.add(
+                                    egui::Slider::new(&mut self.simulation_speed, 0.1..=50.0)
+                                        .logarithmic(true)
+                                        .fixed_decimals(1)
+                                        .suffix(" s/s"),
+                                );
+                                ui.end_row();
+
+                                ui.label("Max substeps:");
+                                ui.add(egui::Slider::new(&mut self.max_substeps, 1..=256));
+                                ui.end_row();
+
+                                ui.label("Scale:");
                                 ui.add(
-                                    egui::Slider::new(&mut self.dp.pendula.0.mass, 5.0..=70.0)
-                                        .fixed_decimals(2),
+                                    egui::Slider::new(&mut self.pixels_per_metre, 20.0..=500.0)
+                                        .logarithmic(true)
+                                        .fixed_decimals(0)
+                                        .suffix(" px/m"),
                                 );
                                 ui.end_row();
 
-                                ui.label("Arm length:");
+                                ui.label("Bob radius:");
                                 ui.add(
-                                    egui::Slider::new(
-                                        &mut self.dp.pendula.0.arm_length,
-                                        10.0..=300.0,
-                                    )
-                                    .fixed_decimals(2),
+                                    egui::Slider::new(&mut self.bob_radius, 2.0..=40.0)
+                                        .fixed_decimals(0)
+                                        .suffix(" px"),
                                 );
+                                ui.end_row();
+
+                                ui.label("Dragging:");
+                                ui.checkbox(&mut self.throw_on_release, "Throw on release")
+                                    .on_hover_text(
+                                        "Give the pendulum the pointer's velocity when letting \
+                                         go, instead of releasing it from rest",
+                                    );
+                                ui.end_row();
+
+                                ui.label("Integrator:");
+                                egui::ComboBox::from_id_source("integrator_combo")
+                                    .selected_text(self.dp.integrator.name())
+                                    .show_ui(ui, |ui| {
+                                        for method in Method::ALL {
+                                            let selected = self.dp.integrator.same_kind(&method);
+                                            if ui
+                                                .selectable_label(selected, method.name())
+                                                .clicked()
+                                                && !selected
+                                            {
+                                                self.dp.integrator = method;
+                                            }
+                                        }
+                                    });
+                                ui.end_row();
+
+                                if let Method::DormandPrince(rk45) = &mut self.dp.integrator {
+                                    ui.label("Abs. tolerance:");
+                                    ui.add(
+                                        egui::Slider::new(
+                                            &mut rk45.absolute_tolerance,
+                                            1e-8..=1e-2,
+                                        )
+                                        .logarithmic(true),
+                                    );
+                                    ui.end_row();
+
+                                    ui.label("Rel. tolerance:");
+                                    ui.add(
+                                        egui::Slider::new(
+                                            &mut rk45.relative_tolerance,
+                                            1e-8..=1e-2,
+                                        )
+                                        .logarithmic(true),
+                                    );
+                                    ui.end_row();
+
+                                    ui.label("Steps:");
+                                    ui.label(format!(
+                                        "{} accepted, {} rejected",
+                                        rk45.accepted_steps, rk45.rejected_steps
+                                    ));
+                                    ui.end_row();
+
+                                    if rk45.forced_steps > 0 {
+                                        ui.label("Tolerance:");
+                                        ui.colored_label(
+                                            ui.visuals().warn_fg_color,
+                                            format!("missed by {} steps", rk45.forced_steps),
+                                        );
+                                        ui.end_row();
+                                    }
+                                }
+
+                                let unconverged = self.dp.integrator.unconverged_stages();
+                                if unconverged > 0 {
+                                    ui.label("Solver:");
+                                    ui.colored_label(
+                                        ui.visuals().warn_fg_color,
+                                        format!("{unconverged} implicit stages did not converge"),
+                                    );
+                                    ui.end_row();
+                                }
                             });
                         ui.separator();
 
-                        ui.heading("Second pendulum");
-                        egui::Grid::new("second_pendulum_grid")
+                        ui.horizontal_wrapped(|ui| {
+                            if ui.button("\u{1F4C2} Open").clicked() {
+                                files::open(SCENARIO_FILTER, self.scenario_sender.clone());
+                            }
+                            if ui.button("\u{1F4BE} Save").clicked() {
+                                self.save_scenario();
+                            }
+                            #[cfg(target_arch = "wasm32")]
+                            if ui.button("\u{1F517} Copy link").clicked() {
+                                self.copy_link(ui.ctx());
+                            }
+                            if ui
+                                .button(format!("\u{1F4C4} Export CSV (last {PLOT_HISTORY} s)"))
+                                .on_hover_text(
+                                    "Export the recorded histories behind the plots, which keep \
+                                     only the end of the run; pendulum-cli simulate writes a \
+                                     whole run",
+                                )
+                                .clicked()
+                            {
+                                self.export_csv();
+                            }
+                            ui.menu_button("\u{2B50} Presets", |ui| {
+                                for preset in PRESETS {
+                                    if ui
+                                        .button(preset.name)
+                                        .on_hover_text(preset.description)
+                                        .clicked()
+                                    {
+                                        self.load_scenario(&preset.scenario());
+                                        self.status = Some(Ok(format!("Loaded {}", preset.name)));
+                                        ui.close_menu();
+                                    }
+                                }
+                            });
+                        });
+                        match &self.status {
+                            Some(Ok(message)) => {
+                                ui.label(message);
+                            }
+                            Some(Err(message)) => {
+                                ui.colored_label(ui.visuals().error_fg_color, message);
+                            }
+                            None => {}
+                        }
+                        ui.separator();
+
+                        ui.heading("Ensemble");
+                        egui::Grid::new("ensemble_grid")
                             .striped(true)
                             .spacing([20.0, 5.0])
                             .show(ui, |ui| {
-                                ui.label("Mass:");
-                                ui.add(
-                                    egui::Slider::new(&mut self.dp.pendula.1.mass, 5.0..=70.0)
-                                        .fixed_decimals(2),
-                                );
+                                ui.label("Size:");
+                                let size =
+                                    ui.add(egui::Slider::new(&mut self.ensemble_size, 0..=256));
                                 ui.end_row();
 
-                                ui.label("Arm length:");
-                                ui.add(
-                                    egui::Slider::new(
-                                        &mut self.dp.pendula.1.arm_length,
-                                        10.0..=300.0,
-                                    )
-                                    .fixed_decimals(2),
+                                ui.label("Perturbation:");
+                                let perturbation = ui.add(
+                                    egui::Slider::new(&mut self.perturbation, 1e-9..=1e-1)
+                                        .logarithmic(true)
+                                        .suffix(" rad"),
                                 );
+                                ui.end_row();
+
+                                if size.changed() || perturbation.changed() {
+                                    self.spawn_ensemble();
+                                }
+                            });
+                        if ui
+                            .add_enabled(self.ensemble_size > 0, egui::Button::new("Respawn"))
+                            .on_hover_text("Restart the ensemble around the current state")
+                            .clicked()
+                        {
+                            self.spawn_ensemble();
+                        }
+                        ui.separator();
+
+                        ui.horizontal(|ui| {
+                            ui.heading("Links");
+                            if ui
+                                .add_enabled(self.dp.links() > 1, egui::Button::new("\u{2796}"))
+                                .clicked()
+                            {
+                                self.dp.remove_link();
+                                self.restart_run();
+                            }
+                            if ui.button("\u{2795}").clicked() {
+                                self.dp.add_link();
+                                self.restart_run();
+                            }
+                        });
+
+                        egui::ScrollArea::vertical()
+                            .max_height(0.4 * ctx.screen_rect().height())
+                            .show(ui, |ui| {
+                                for (i, pendulum) in self.dp.pendula.iter_mut().enumerate() {
+                                    ui.label(
+                                        egui::RichText::new(format!("Pendulum {}", i + 1)).strong(),
+                                    );
+                                    egui::Grid::new(("pendulum_grid", i))
+                                        .striped(true)
+                                        .spacing([20.0, 5.0])
+                                        .show(ui, |ui| {
+                                            ui.label("Mass:");
+                                            ui.add(
+                                                egui::Slider::new(&mut pendulum.mass, 0.1..=10.0)
+                                                    .fixed_decimals(2)
+                                                    .suffix(" kg"),
+                                            );
+                                            ui.end_row();
+
+                                            ui.label("Arm length:");
+                                            ui.add(
+                                                egui::Slider::new(
+                                                    &mut pendulum.arm_length,
+                                                    0.1..=3.0,
+                                                )
+                                                .fixed_decimals(2)
+                                                .suffix(" m"),
+                                            );
+                                            ui.end_row();
+
+                                            ui.label("Joint damping:");
+                                            ui.add(
+                                                egui::Slider::new(
+                                                    &mut pendulum.joint_damping,
+                                                    0.0..=1.0,
+                                                )
+                                                .fixed_decimals(3)
+                                                .suffix(" N·m·s"),
+                                            );
+                                        });
+                                }
                             });
 
                         ui.separator();
@@ -237,6 +829,14 @@ impl App {
                                     ui.label("Ctrl+Alt+R");
                                     ui.end_row();
 
+                                    ui.label("Step back:");
+                                    ui.label("←");
+                                    ui.end_row();
+
+                                    ui.label("Step forward:");
+                                    ui.label("→");
+                                    ui.end_row();
+
                                     ui.label("Zoom in:");
                                     ui.label("Ctrl++");
                                     ui.end_row();
@@ -262,6 +862,64 @@ impl App {
             });
     }
 
+    /// Playback controls and a slider over the recorded snapshots.
+    fn timeline_bar(&mut self, ctx: &egui::Context) {
+        egui::TopBottomPanel::bottom("timeline").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                if ui
+                    .button("⏮")
+                    .on_hover_text("Step back one time step (←)")
+                    .clicked()
+                {
+                    self.step_back();
+                }
+                let play = if self.running { "⏸" } else { "▶" };
+                if ui
+                    .button(play)
+                    .on_hover_text("Pause or resume (Space)")
+                    .clicked()
+                {
+                    self.running = !self.running;
+                }
+                if ui
+                    .button("⏭")
+                    .on_hover_text("Step forward one time step (→)")
+                    .clicked()
+                {
+                    self.step_forward();
+                }
+
+                egui::ComboBox::from_id_source("playback_speed")
+                    .width(60.0)
+                    .selected_text(format!("{}×", self.simulation_speed))
+                    .show_ui(ui, |ui| {
+                        for speed in [0.1, 0.25, 0.5, 1.0, 2.0, 4.0, 10.0] {
+                            ui.selectable_value(
+                                &mut self.simulation_speed,
+                                speed,
+                                format!("{speed}×"),
+                            );
+                        }
+                    })
+                    .response
+                    .on_hover_text("Playback speed");
+                ui.monospace(format!("t = {:.3} s", self.time));
+
+                if let (Some(start), Some(end)) = (self.timeline.start(), self.timeline.end()) {
+                    let mut time = self.time;
+                    ui.spacing_mut().slider_width = ui.available_width();
+                    let response = ui.add(
+                        egui::Slider::new(&mut time, start..=end.max(start + f64::EPSILON))
+                            .show_value(false),
+                    );
+                    if response.changed() {
+                        self.rewind_to(time);
+                    }
+                }
+            });
+        });
+    }
+
     fn canvas(&mut self, ctx: &egui::Context) {
         egui::CentralPanel::default().show(ctx, |ui| {
             let (response, painter) = ui.allocate_painter(
@@ -273,16 +931,18 @@ impl App {
                 },
             );
 
-            self.canvas_size = response.rect.size();
             self.canvas_transform = emath::RectTransform::from_to(
-                egui::Rect::from_min_size(egui::Pos2::ZERO, response.rect.size()),
+                egui::Rect::from_center_size(
+                    egui::Pos2::ZERO,
+                    response.rect.size() / self.pixels_per_metre,
+                ),
                 response.rect,
             );
 
             painter.add(egui::Shape::line(
                 self.position_history
                     .iter()
-                    .map(|(_, p)| p.1 + self.dp.pendula.0.pivot.to_vec2())
+                    .filter_map(|(_, p)| p.last().map(|p| self.canvas_transform * *p))
                     .collect(),
                 egui::Stroke {
                     width: 1.0,
@@ -290,16 +950,63 @@ impl App {
                 },
             ));
 
-            self.paint_pendulum(&painter, &self.dp.pendula.0);
-            self.paint_pendulum(&painter, &self.dp.pendula.1);
+            self.paint_ensemble(&painter);
+            for pendulum in &self.dp.pendula {
+                self.paint_pendulum(&painter, pendulum);
+            }
+
+            let counters = self
+                .dp
+                .pendula
+                .iter()
+                .enumerate()
+                .map(|(i, p)| format!("Pendulum {}: {} flips, {:+} turns", i + 1, p.flips, p.turns))
+                .collect::<Vec<_>>()
+                .join("\n");
+            painter.text(
+                response.rect.left_top() + egui::vec2(10.0, 10.0),
+                egui::Align2::LEFT_TOP,
+                counters,
+                egui::FontId::monospace(12.0),
+                egui::Color32::GRAY,
+            );
         });
     }
 
+    fn spectrum(&mut self, ctx: &egui::Context) {
+        egui::CentralPanel::default().show(ctx, |ui| self.spectrum.ui(ui, &self.dp));
+    }
+
+    fn flip_map(&mut self, ctx: &egui::Context) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            if let Some(angles) = self.flip_map.ui(ui, &self.dp, self.time_step) {
+                self.release_from(angles);
+            }
+        });
+    }
+
+    /// Releases the pendulum from rest with the first two arms at `angles` and any others hanging
+    /// down, and switches to it.
+    fn release_from(&mut self, angles: [f32; 2]) {
+        let mut state = State::zeros(self.dp.links());
+        state.angles[..2].copy_from_slice(&angles);
+        self.dp.set_state(&state);
+        self.dp
+            .pendula
+            .iter_mut()
+            .for_each(Pendulum::reset_counters);
+
+        self.accumulator = 0.0;
+        self.restart_run();
+        self.running = true;
+        self.current_tab = Tab::Pendulum;
+    }
+
     fn paint_pendulum(&self, painter: &egui::Painter, pendulum: &Pendulum) {
         painter.line_segment(
             [
-                self.canvas_transform * pendulum.pivot,
-                self.canvas_transform * pendulum.position(),
+                self.canvas_transform * egui::Pos2::from(pendulum.pivot),
+                self.canvas_transform * egui::Pos2::from(pendulum.position()),
             ],
             egui::Stroke {
                 width: 3.0,
@@ -308,12 +1015,40 @@ impl App {
         );
 
         painter.circle_filled(
-            self.canvas_transform * pendulum.position(),
-            pendulum.mass * MASS_COEFFICIENT,
+            self.canvas_transform * egui::Pos2::from(pendulum.position()),
+            self.bob_radius,
             egui::Color32::WHITE,
         );
     }
 
+    /// Draws the ensemble members thinly, coloured by their initial offset.
+    fn paint_ensemble(&self, painter: &egui::Painter) {
+        for (i, member) in self.ensemble.iter().enumerate() {
+            let hue = if self.ensemble.len() > 1 {
+                0.8 * i as f32 / (self.ensemble.len() - 1) as f32
+            } else {
+                0.0
+            };
+            let colour = egui::Color32::from(egui::ecolor::Hsva::new(hue, 0.8, 0.9, 0.6));
+
+            let mut points =
+                vec![self.canvas_transform * egui::Pos2::from(member.pendula[0].pivot)];
+            points.extend(
+                member
+                    .pendula
+                    .iter()
+                    .map(|p| self.canvas_transform * egui::Pos2::from(p.position())),
+            );
+            painter.add(egui::Shape::line(
+                points.clone(),
+                egui::Stroke::new(1.0, colour),
+            ));
+            for point in points.into_iter().skip(1) {
+                painter.circle_filled(point, 0.3 * self.bob_radius, colour);
+            }
+        }
+    }
+
     fn plots(&mut self, ctx: &egui::Context) {
         egui::CentralPanel::default().show(ctx, |ui| {
             egui::TopBottomPanel::top("graph_tabs").show_inside(ui, |ui| {
@@ -338,6 +1073,36 @@ impl App {
                         Plot::Position,
                         egui::RichText::new("Positions").heading(),
                     );
+                    ui.selectable_value(
+                        &mut self.current_plot,
+                        Plot::Phase,
+                        egui::RichText::new("Phase space").heading(),
+                    );
+                    ui.selectable_value(
+                        &mut self.current_plot,
+                        Plot::Configuration,
+                        egui::RichText::new("Configuration").heading(),
+                    );
+                    ui.selectable_value(
+                        &mut self.current_plot,
+                        Plot::Energy,
+                        egui::RichText::new("Energy").heading(),
+                    );
+                    ui.selectable_value(
+                        &mut self.current_plot,
+                        Plot::Spread,
+                        egui::RichText::new("Ensemble spread").heading(),
+                    );
+                    ui.selectable_value(
+                        &mut self.current_plot,
+                        Plot::Lyapunov,
+                        egui::RichText::new("Lyapunov").heading(),
+                    );
+                    ui.selectable_value(
+                        &mut self.current_plot,
+                        Plot::Poincare,
+                        egui::RichText::new("Poincaré section").heading(),
+                    );
                 });
             });
 
@@ -346,23 +1111,17 @@ impl App {
                 Plot::Velocity => self.velocity_plot(ui),
                 Plot::Acceleration => self.acceleration_plot(ui),
                 Plot::Position => self.position_plot(ui),
+                Plot::Phase => self.phase_plot(ui),
+                Plot::Configuration => self.configuration_plot(ui),
+                Plot::Energy => self.energy_plot(ui),
+                Plot::Spread => self.spread_plot(ui),
+                Plot::Lyapunov => self.lyapunov_plot(ui),
+                Plot::Poincare => self.poincare.ui(ui, &self.dp),
             });
         });
     }
 
     fn angle_plot(&self, ui: &mut egui::Ui) {
-        let theta1: egui_plot::PlotPoints = self
-            .angle_history
-            .iter()
-            .map(|(time, (theta, _))| [time, theta as f64])
-            .collect();
-
-        let theta2: egui_plot::PlotPoints = self
-            .angle_history
-            .iter()
-            .map(|(time, (_, theta))| [time, theta as f64])
-            .collect();
-
         egui_plot::Plot::new("angle")
             .center_y_axis(true)
             .allow_zoom(true)
@@ -377,114 +1136,320 @@ impl App {
                     format!("{}π", value / PI)
                 }
             })
-            .x_axis_label("Time")
+            .x_axis_label("Time (s)")
             .y_axis_label("Angle")
             .legend(egui_plot::Legend::default())
             .show(ui, |plot_ui| {
-                plot_ui.line(egui_plot::Line::new(theta1).name("First pendulum θ"));
-                plot_ui.line(egui_plot::Line::new(theta2).name("Second pendulum θ"));
+                for link in 0..self.dp.links() {
+                    plot_ui.line(
+                        egui_plot::Line::new(link_series(&self.angle_history, link))
+                            .name(format!("Pendulum {} θ", link + 1)),
+                    );
+                }
             });
     }
 
     fn velocity_plot(&self, ui: &mut egui::Ui) {
-        let v1: egui_plot::PlotPoints = self
-            .velocity_history
-            .iter()
-            .map(|(time, (v, _))| [time, v as f64])
-            .collect();
-
-        let v2: egui_plot::PlotPoints = self
-            .velocity_history
-            .iter()
-            .map(|(time, (_, v))| [time, v as f64])
-            .collect();
-
         egui_plot::Plot::new("velocity")
             .center_y_axis(true)
             .allow_zoom(true)
             .allow_scroll(false)
             .allow_drag(false)
-            .x_axis_label("Time")
+            .x_axis_label("Time (s)")
             .y_axis_label("Velocity")
             .legend(egui_plot::Legend::default())
             .show(ui, |plot_ui| {
-                plot_ui.line(egui_plot::Line::new(v1).name("First pendulum velocity"));
-                plot_ui.line(egui_plot::Line::new(v2).name("Second pendulum velocity"));
+                for link in 0..self.dp.links() {
+                    plot_ui.line(
+                        egui_plot::Line::new(link_series(&self.velocity_history, link))
+                            .name(format!("Pendulum {} velocity", link + 1)),
+                    );
+                }
             });
     }
 
     fn acceleration_plot(&self, ui: &mut egui::Ui) {
-        let a1: egui_plot::PlotPoints = self
-            .acceleration_history
+        egui_plot::Plot::new("acceleration")
+            .center_y_axis(true)
+            .allow_zoom(true)
+            .allow_scroll(false)
+            .allow_drag(false)
+            .x_axis_label("Time (s)")
+            .y_axis_label("Acceleration")
+            .legend(egui_plot::Legend::default())
+            .show(ui, |plot_ui| {
+                for link in 0..self.dp.links() {
+                    plot_ui.line(
+                        egui_plot::Line::new(link_series(&self.acceleration_history, link))
+                            .name(format!("Pendulum {} acceleration", link + 1)),
+                    );
+                }
+            });
+    }
+
+    fn position_plot(&self, ui: &mut egui::Ui) {
+        egui_plot::Plot::new("velocities")
+            .legend(egui_plot::Legend::default())
+            .show(ui, |plot_ui| {
+                for link in 0..self.dp.links() {
+                    let points: egui_plot::PlotPoints = self
+                        .position_history
+                        .iter()
+                        .filter_map(|(_, positions)| positions.get(link))
+                        .map(|pos| [pos.x as f64, -pos.y as f64])
+                        .collect();
+
+                    plot_ui.line(
+                        egui_plot::Line::new(points)
+                            .name(format!("Pendulum {} position", link + 1)),
+                    );
+                }
+            });
+    }
+
+    /// Phase portrait of one arm: its angle, wrapped to ±π, against its angular velocity.
+    fn phase_plot(&mut self, ui: &mut egui::Ui) {
+        let links = self.dp.links();
+        self.phase_link = self.phase_link.min(links - 1);
+        ui.horizontal(|ui| {
+            ui.label("Pendulum");
+            egui::ComboBox::from_id_source("phase_link")
+                .width(40.0)
+                .selected_text((self.phase_link + 1).to_string())
+                .show_ui(ui, |ui| {
+                    for link in 0..links {
+                        ui.selectable_value(&mut self.phase_link, link, (link + 1).to_string());
+                    }
+                });
+        });
+
+        let link = self.phase_link;
+        let trajectory = self
+            .angle_history
             .iter()
-            .map(|(time, (a, _))| [time, a as f64])
+            .zip(self.velocity_history.iter())
+            .filter_map(|((_, angles), (_, velocities))| {
+                Some([*angles.get(link)? as f64, *velocities.get(link)? as f64])
+            });
+        let segments = wrapped_segments(trajectory, [true, false]);
+        let current = self
+            .dp
+            .pendula
+            .get(link)
+            .map(|p| [p.angle as f64, p.velocity as f64]);
+
+        egui_plot::Plot::new("phase")
+            .allow_zoom(true)
+            .allow_scroll(false)
+            .allow_drag(false)
+            .include_x(-PI)
+            .include_x(PI)
+            .x_grid_spacer(egui_plot::uniform_grid_spacer(|_| [TAU, PI, PI / 4.0]))
+            .x_axis_formatter(|value, _, _| format!("{}π", value / PI))
+            .x_axis_label(format!("θ{}", link + 1))
+            .y_axis_label(format!("ω{} (rad/s)", link + 1))
+            .show(ui, |plot_ui| {
+                for segment in segments {
+                    plot_ui.line(egui_plot::Line::new(segment).color(egui::Color32::LIGHT_BLUE));
+                }
+                if let Some(current) = current {
+                    plot_ui.points(egui_plot::Points::new(current).radius(4.0));
+                }
+            });
+    }
+
+    /// Trajectory on the configuration torus of the first two arms, both angles wrapped to ±π.
+    fn configuration_plot(&self, ui: &mut egui::Ui) {
+        if self.dp.links() < 2 {
+            ui.centered_and_justified(|ui| {
+                ui.label("The configuration plot needs at least two links.")
+            });
+            return;
+        }
+
+        let trajectory = self
+            .angle_history
+            .iter()
+            .filter_map(|(_, angles)| Some([*angles.first()? as f64, *angles.get(1)? as f64]));
+        let segments = wrapped_segments(trajectory, [true, true]);
+        let current = [
+            self.dp.pendula[0].angle as f64,
+            self.dp.pendula[1].angle as f64,
+        ];
+
+        let formatter =
+            |value: f64, _: usize, _: &std::ops::RangeInclusive<f64>| format!("{}π", value / PI);
+        egui_plot::Plot::new("configuration")
+            .data_aspect(1.0)
+            .allow_zoom(true)
+            .allow_scroll(false)
+            .allow_drag(false)
+            .include_x(-PI)
+            .include_x(PI)
+            .include_y(-PI)
+            .include_y(PI)
+            .x_grid_spacer(egui_plot::uniform_grid_spacer(|_| [TAU, PI, PI / 4.0]))
+            .y_grid_spacer(egui_plot::uniform_grid_spacer(|_| [TAU, PI, PI / 4.0]))
+            .x_axis_formatter(formatter)
+            .y_axis_formatter(formatter)
+            .x_axis_label("θ1")
+            .y_axis_label("θ2")
+            .show(ui, |plot_ui| {
+                for segment in segments {
+                    plot_ui.line(egui_plot::Line::new(segment).color(egui::Color32::LIGHT_BLUE));
+                }
+                plot_ui.points(egui_plot::Points::new(current).radius(4.0));
+            });
+    }
+
+    fn energy_plot(&self, ui: &mut egui::Ui) {
+        let kinetic: egui_plot::PlotPoints = self
+            .energy_history
+            .iter()
+            .map(|(time, &(kinetic, _))| [time, kinetic as f64])
             .collect();
 
-        let a2: egui_plot::PlotPoints = self
-            .acceleration_history
+        let potential: egui_plot::PlotPoints = self
+            .energy_history
             .iter()
-            .map(|(time, (_, a))| [time, a as f64])
+            .map(|(time, &(_, potential))| [time, potential as f64])
             .collect();
 
-        egui_plot::Plot::new("acceleration")
+        let total: egui_plot::PlotPoints = self
+            .energy_history
+            .iter()
+            .map(|(time, &(kinetic, potential))| [time, (kinetic + potential) as f64])
+            .collect();
+
+        let drift: egui_plot::PlotPoints = self
+            .energy_drift_history
+            .iter()
+            .map(|(time, &drift)| [time, drift as f64])
+            .collect();
+
+        ui.horizontal(|ui| {
+            ui.label(format!("Total energy: {:.4e}", self.dp.total_energy()));
+            ui.separator();
+            ui.label(format!(
+                "Angular momentum: {:.4e}",
+                self.dp.angular_momentum()
+            ));
+        });
+
+        let height = 0.5 * ui.available_height();
+
+        egui_plot::Plot::new("energy")
+            .height(height)
+            .allow_zoom(true)
+            .allow_scroll(false)
+            .allow_drag(false)
+            .link_axis("energy_axes", true, false)
+            .x_axis_label("Time (s)")
+            .y_axis_label("Energy")
+            .legend(egui_plot::Legend::default())
+            .show(ui, |plot_ui| {
+                plot_ui.line(egui_plot::Line::new(kinetic).name("Kinetic energy"));
+                plot_ui.line(egui_plot::Line::new(potential).name("Potential energy"));
+                plot_ui.line(egui_plot::Line::new(total).name("Total energy"));
+            });
+
+        egui_plot::Plot::new("energy_drift")
             .center_y_axis(true)
             .allow_zoom(true)
             .allow_scroll(false)
             .allow_drag(false)
-            .x_axis_label("Time")
-            .y_axis_label("Acceleration")
+            .link_axis("energy_axes", true, false)
+            .x_axis_label("Time (s)")
+            .y_axis_label("Relative drift")
             .legend(egui_plot::Legend::default())
             .show(ui, |plot_ui| {
-                plot_ui.line(egui_plot::Line::new(a1).name("First pendulum acceleration"));
-                plot_ui.line(egui_plot::Line::new(a2).name("Second pendulum acceleration"));
+                plot_ui.line(egui_plot::Line::new(drift).name("Relative energy drift"));
             });
     }
 
-    fn position_plot(&self, ui: &mut egui::Ui) {
-        let p1: egui_plot::PlotPoints = self
-            .position_history
+    /// Spread of the ensemble on a logarithmic axis, where exponential divergence is a line.
+    fn spread_plot(&self, ui: &mut egui::Ui) {
+        if self.ensemble.is_empty() {
+            ui.label("Set an ensemble size in the settings to see how nearby starts diverge.");
+            return;
+        }
+
+        let spread: egui_plot::PlotPoints = self
+            .spread_history
             .iter()
-            .map(|(_, (pos, _))| [pos.x as f64, -pos.y as f64])
+            .filter(|(_, &spread)| spread > 0.0)
+            .map(|(time, &spread)| [time, (spread as f64).log10()])
             .collect();
 
-        let p2: egui_plot::PlotPoints = self
-            .position_history
+        egui_plot::Plot::new("spread")
+            .allow_zoom(true)
+            .allow_scroll(false)
+            .allow_drag(false)
+            .y_grid_spacer(egui_plot::uniform_grid_spacer(|_| [10.0, 5.0, 1.0]))
+            .y_axis_formatter(|value, _, _| format!("1e{value}"))
+            .x_axis_label("Time (s)")
+            .y_axis_label("Spread (m)")
+            .legend(egui_plot::Legend::default())
+            .show(ui, |plot_ui| {
+                plot_ui.line(egui_plot::Line::new(spread).name("RMS spread of the last bobs"));
+            });
+    }
+
+    fn lyapunov_plot(&self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            match self.lyapunov.exponent() {
+                Some(exponent) => ui.label(format!(
+                    "Largest Lyapunov exponent: {exponent:.3} s⁻¹ after {:.0} s",
+                    self.lyapunov.time()
+                )),
+                None => ui.label("Largest Lyapunov exponent: not yet estimated"),
+            };
+            ui.separator();
+            ui.label("Regular motion tends to zero, chaotic motion to a positive value.");
+        });
+
+        let estimate: egui_plot::PlotPoints = self
+            .lyapunov_history
             .iter()
-            .map(|(_, (_, pos))| [pos.x as f64, -pos.y as f64])
+            .map(|(time, &exponent)| [time, exponent as f64])
             .collect();
 
-        egui_plot::Plot::new("velocities")
+        egui_plot::Plot::new("lyapunov")
+            .allow_zoom(true)
+            .allow_scroll(false)
+            .allow_drag(false)
+            .x_axis_label("Time (s)")
+            .y_axis_label("Exponent (1/s)")
             .legend(egui_plot::Legend::default())
             .show(ui, |plot_ui| {
-                plot_ui.line(egui_plot::Line::new(p1).name("First pendulum position"));
-                plot_ui.line(egui_plot::Line::new(p2).name("Second pendulum position"));
+                plot_ui.hline(egui_plot::HLine::new(0.0).color(egui::Color32::GRAY));
+                plot_ui.line(egui_plot::Line::new(estimate).name("Running estimate"));
             });
     }
 
     fn move_pendula(&mut self, ctx: &egui::Context) {
+        let mut released = None;
         let pointer_position = ctx.input(|i| {
             if i.pointer.primary_released() {
-                self.moving.0 = false;
-                self.moving.1 = false;
+                released = self.moving.take().map(|link| (link, i.pointer.velocity()));
             }
 
             if let Some(pointer_position) = i.pointer.latest_pos().map(|p| p.to_vec2()) {
                 if i.pointer.primary_pressed() {
-                    if (self.canvas_transform * self.dp.pendula.0.position() - pointer_position)
-                        .to_vec2()
-                        .length_sq()
-                        < (self.dp.pendula.0.mass * MASS_COEFFICIENT).powi(2)
-                    {
-                        self.moving.0 = true;
-                    }
-
-                    if (self.canvas_transform * self.dp.pendula.1.position() - pointer_position)
-                        .to_vec2()
-                        .length_sq()
-                        < (self.dp.pendula.1.mass * MASS_COEFFICIENT).powi(2)
-                    {
-                        self.moving.1 = true;
-                    }
+                    self.moving = self
+                        .dp
+                        .pendula
+                        .iter()
+                        .map(|p| {
+                            (self.canvas_transform * egui::Pos2::from(p.position())
+                                - pointer_position)
+                                .to_vec2()
+                                .length_sq()
+                        })
+                        .enumerate()
+                        .filter(|(_, distance_sq)| *distance_sq < self.bob_radius.powi(2))
+                        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+                        .map(|(i, _)| i);
                 }
 
                 pointer_position
@@ -493,94 +1458,309 @@ impl App {
             }
         });
 
-        if self.moving.0 {
-            self.dp.pendula.0.angle = (pointer_position
-                - (self.canvas_transform * self.dp.pendula.0.pivot).to_vec2())
-            .yx()
-            .angle();
-            self.dp.pendula.0.acceleration = 0.0;
-            self.dp.pendula.0.velocity = 0.0;
-
-            self.dp.pendula.1.pivot = self.canvas_transform * self.dp.pendula.0.position();
-            self.dp.pendula.1.acceleration = 0.0;
-            self.dp.pendula.1.velocity = 0.0;
-            self.position_history.clear();
-        }
+        if let Some(link) = self.moving {
+            let pivot = self.canvas_transform * egui::Pos2::from(self.dp.pendula[link].pivot);
+            self.dp.pendula[link].angle = (pointer_position - pivot.to_vec2()).yx().angle();
 
-        if self.moving.1 {
-            self.dp.pendula.0.acceleration = 0.0;
-            self.dp.pendula.0.velocity = 0.0;
+            for pendulum in &mut self.dp.pendula {
+                pendulum.acceleration = 0.0;
+                pendulum.velocity = 0.0;
+                pendulum.reset_counters();
+            }
 
-            self.dp.pendula.1.angle = (pointer_position
-                - (self.canvas_transform * self.dp.pendula.1.pivot).to_vec2())
-            .yx()
-            .angle();
-            self.dp.pendula.1.acceleration = 0.0;
-            self.dp.pendula.1.velocity = 0.0;
+            self.dp.update_pivots();
             self.position_history.clear();
+            self.reference_energy = None;
+            self.timeline.record(self.time, &self.dp);
+            self.restart_analyses();
         }
+
+        if let Some((link, velocity)) = released.filter(|_| self.throw_on_release) {
+            self.throw(link, velocity);
+        }
+    }
+
+    /// Sets the pendulum moving as if bob `link` had been flicked at `pointer_velocity`, in
+    /// points per second of wall clock.
+    fn throw(&mut self, link: usize, pointer_velocity: egui::Vec2) {
+        let velocity = pointer_velocity / (self.pixels_per_metre * self.simulation_speed);
+        let mut state = self.dp.state();
+        state.velocities = self
+            .dp
+            .velocities_for_bob_velocity(link, [velocity.x, velocity.y]);
+        self.dp.set_state(&state);
+
+        self.timeline.record(self.time, &self.dp);
+        self.restart_analyses();
     }
 
     fn record_history(&mut self) {
-        #[cfg(not(target_arch = "wasm32"))]
-        let now = self.epoch.elapsed().as_millis() as f64 / 1000.0;
+        let now = self.time;
+        // Nothing happened since the last sample while paused, so a pause leaves no gap.
+        if self.angle_history.latest_time() == Some(now) {
+            return;
+        }
 
-        #[cfg(target_arch = "wasm32")]
-        let now = eframe::web::now_sec();
+        let pendula = &self.dp.pendula;
 
-        self.position_history.add(
-            now,
-            (
-                self.canvas_transform * self.dp.pendula.0.position()
-                    - self.dp.pendula.0.pivot.to_vec2(),
-                self.canvas_transform * self.dp.pendula.1.position()
-                    - self.dp.pendula.0.pivot.to_vec2(),
-            ),
-        );
+        self.position_history
+            .add(now, pendula.iter().map(|p| p.position().into()).collect());
+        self.angle_history
+            .add(now, pendula.iter().map(|p| p.unwrapped_angle()).collect());
+        self.velocity_history
+            .add(now, pendula.iter().map(|p| p.velocity).collect());
+        self.acceleration_history
+            .add(now, pendula.iter().map(|p| p.acceleration).collect());
 
-        self.angle_history.add(
-            now,
-            (
-                self.dp.pendula.0.angle.sin().asin(),
-                self.dp.pendula.1.angle.sin().asin(),
-            ),
-        );
+        let kinetic = self.dp.kinetic_energy();
+        let potential = self.dp.potential_energy();
+        self.energy_history.add(now, (kinetic, potential));
 
-        self.velocity_history.add(
-            now,
-            (self.dp.pendula.0.velocity, self.dp.pendula.1.velocity),
-        );
+        if let Some(spread) = self.ensemble_spread() {
+            self.spread_history.add(now, spread);
+        }
+        if let Some(exponent) = self.lyapunov.exponent() {
+            self.lyapunov_history.add(now, exponent);
+        }
 
-        self.acceleration_history.add(
-            now,
-            (
-                self.dp.pendula.0.acceleration,
-                self.dp.pendula.1.acceleration,
-            ),
-        );
+        let reference = *self.reference_energy.get_or_insert(kinetic + potential);
+        if reference > 0.0 {
+            self.energy_drift_history
+                .add(now, (kinetic + potential - reference) / reference);
+        }
     }
 
     #[inline]
     fn moving(&self) -> bool {
-        self.moving.0 || self.moving.1
+        self.moving.is_some()
     }
 
-    #[cfg(not(target_arch = "wasm32"))]
-    fn reset(&mut self) {
-        self.dp = DoublePendulum::default();
-        self.position_history.clear();
-        self.angle_history.clear();
-        self.velocity_history.clear();
-        self.acceleration_history.clear();
-        self.epoch = std::time::Instant::now();
+    /// Starts a new run from the current state at time zero, clearing the histories and the
+    /// timeline.
+    fn restart_run(&mut self) {
+        self.time = 0.0;
+        self.timeline.clear();
+        self.timeline.record(self.time, &self.dp);
+        self.clear_history();
+        self.restart_analyses();
+    }
+
+    /// Starts the analyses over from the current state: respawns the ensemble, restarts the
+    /// Lyapunov estimate and the spectra, and begins a new orbit on the Poincaré section, keeping
+    /// the old ones.
+    fn restart_analyses(&mut self) {
+        self.spawn_ensemble();
+        self.restart_lyapunov();
+        self.poincare.new_run();
+        self.spectrum.clear();
+    }
+
+    fn restart_lyapunov(&mut self) {
+        self.lyapunov = LyapunovEstimator::new(&self.dp);
+        self.lyapunov_history.clear();
+    }
+
+    /// Replaces the ensemble with `ensemble_size` copies of `dp`, their angles all offset by
+    /// evenly spaced amounts between `-perturbation` and `perturbation`.
+    fn spawn_ensemble(&mut self) {
+        let state = self.dp.state();
+        let size = self.ensemble_size;
+
+        self.ensemble = (0..size)
+            .map(|i| {
+                let offset = if size > 1 {
+                    self.perturbation * (2.0 * i as f32 / (size - 1) as f32 - 1.0)
+                } else {
+                    self.perturbation
+                };
+
+                let mut member = self.dp.clone();
+                member.set_state(&State {
+                    angles: state.angles.iter().map(|angle| angle + offset).collect(),
+                    velocities: state.velocities.clone(),
+                });
+                member
+            })
+            .collect();
+        self.spread_history.clear();
+    }
+
+    /// RMS distance of the ensemble's last bobs from their centroid, in metres.
+    fn ensemble_spread(&self) -> Option<f32> {
+        let positions: Vec<[f32; 2]> = self
+            .ensemble
+            .iter()
+            .filter_map(|member| member.pendula.last().map(Pendulum::position))
+            .collect();
+        if positions.is_empty() {
+            return None;
+        }
+
+        let n = positions.len() as f32;
+        let centroid = positions
+            .iter()
+            .fold([0.0, 0.0], |[x, y], [px, py]| [x + px / n, y + py / n]);
+        let variance = positions
+            .iter()
+            .map(|[x, y]| (x - centroid[0]).powi(2) + (y - centroid[1]).powi(2))
+            .sum::<f32>()
+            / n;
+        Some(variance.sqrt())
     }
 
-    #[cfg(target_arch = "wasm32")]
     fn reset(&mut self) {
-        self.dp = DoublePendulum::default();
+        self.dp = NPendulum::new(self.dp.links());
+        self.restart_run();
+    }
+
+    fn clear_history(&mut self) {
         self.position_history.clear();
         self.angle_history.clear();
         self.velocity_history.clear();
         self.acceleration_history.clear();
+        self.energy_history.clear();
+        self.energy_drift_history.clear();
+        self.reference_energy = None;
+    }
+}
+
+/// Splits a trajectory into lines with the coordinates flagged in `angles` wrapped to ±π. Where a
+/// wrapped coordinate passes ±π the line is cut at the edge and continues from the opposite one,
+/// instead of jumping across the plot. Points that are not finite break the line and are left out.
+fn wrapped_segments(
+    points: impl Iterator<Item = [f64; 2]>,
+    angles: [bool; 2],
+) -> Vec<Vec<[f64; 2]>> {
+    // Which 2π-wide copy of the plot each coordinate of a point is in.
+    let cell = |point: [f64; 2], axis: usize| {
+        if angles[axis] {
+            ((point[axis] + PI) / TAU).floor()
+        } else {
+            0.0
+        }
+    };
+    let wrap =
+        |point: [f64; 2], cells: [f64; 2]| [point[0] - TAU * cells[0], point[1] - TAU * cells[1]];
+
+    let mut segments = Vec::new();
+    let mut segment = Vec::new();
+    let mut previous: Option<([f64; 2], [f64; 2])> = None;
+    for point in points {
+        if !point.iter().all(|x| x.is_finite()) {
+            if !segment.is_empty() {
+                segments.push(std::mem::take(&mut segment));
+            }
+            previous = None;
+            continue;
+        }
+
+        let target = [cell(point, 0), cell(point, 1)];
+        let Some((mut from, mut cells)) = previous else {
+            segment.push(wrap(point, target));
+            previous = Some((point, target));
+            continue;
+        };
+
+        // Step through every edge between the two points, nearest first.
+        while cells != target {
+            let (axis, t) = (0..2)
+                .filter(|&axis| cells[axis] != target[axis])
+                .map(|axis| {
+                    let step = (target[axis] - cells[axis]).signum();
+                    let edge = TAU * cells[axis] + PI * step;
+                    (axis, (edge - from[axis]) / (point[axis] - from[axis]))
+                })
+                .min_by(|(_, a), (_, b)| a.total_cmp(b))
+                .expect("the cells differ in some axis");
+
+            let edge = [
+                from[0] + t * (point[0] - from[0]),
+                from[1] + t * (point[1] - from[1]),
+            ];
+            segment.push(wrap(edge, cells));
+            segments.push(std::mem::take(&mut segment));
+
+            cells[axis] += (target[axis] - cells[axis]).signum();
+            segment.push(wrap(edge, cells));
+            from = edge;
+        }
+
+        segment.push(wrap(point, target));
+        previous = Some((point, target));
+    }
+
+    if !segment.is_empty() || segments.is_empty() {
+        segments.push(segment);
+    }
+    segments
+}
+
+/// Time series of a single link's entry in a per-link history.
+fn link_series(history: &History<Vec<f32>>, link: usize) -> egui_plot::PlotPoints {
+    history
+        .iter()
+        .filter_map(|(time, values)| values.get(link).map(|value| [time, *value as f64]))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wrapped_segments_break_at_non_finite_points() {
+        let points = [
+            [0.0, 0.0],
+            [3.0, 0.0],
+            [f64::NAN, 0.0],
+            [3.5, 0.0],
+            [f64::INFINITY, 1.0],
+            [-3.0, 1.0],
+        ];
+
+        let segments = wrapped_segments(points.into_iter(), [true, true]);
+        assert_eq!(
+            segments,
+            vec![
+                vec![[0.0, 0.0], [3.0, 0.0]],
+                vec![[3.5 - TAU, 0.0]],
+                vec![[-3.0, 1.0]]
+            ]
+        );
+    }
+
+    #[test]
+    fn wrapped_segments_cut_at_the_edge() {
+        let segments = wrapped_segments([[3.0, 0.0], [3.5, 0.0]].into_iter(), [true, false]);
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[0].last().unwrap()[0], PI);
+        assert_eq!(segments[1][0][0], -PI);
+    }
+
+    #[test]
+    fn history_csv_states_the_window_it_covers() {
+        let mut app = App::default();
+        let duration = PLOT_HISTORY + 5.0;
+        for _ in 0..(duration / app.time_step).round() as usize {
+            app.step();
+            app.record_history();
+        }
+
+        let csv = app.history_csv().unwrap();
+        let window = csv
+            .lines()
+            .find_map(|line| line.strip_prefix("# window: t = "))
+            .unwrap();
+        let (first, rest) = window.split_once(" to ").unwrap();
+        let (last, _) = rest.split_once(" s;").unwrap();
+        let [first, last] = [first, last].map(|time| time.parse::<f64>().unwrap());
+        assert!((first - 5.0).abs() < 0.01, "window starts at {first} s");
+        assert!(
+            (last - f64::from(duration)).abs() < 0.01,
+            "window ends at {last} s"
+        );
+
+        let first_row = csv.lines().find(|line| !line.starts_with('#')).unwrap();
+        assert!(first_row.starts_with("time,"));
     }
 }
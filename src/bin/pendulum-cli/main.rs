@@ -0,0 +1,329 @@
+//! Command-line interface that runs the simulation headless. It depends on the library alone, so
+//! it builds without the GUI.
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use double_pendulum::{
+    integrator::Method,
+    pendulum::{NPendulum, Pendulum, State},
+    preset::PRESETS,
+    scenario::{Scenario, ScenarioError, SimulationSettings},
+    trajectory::{round_time, Sample},
+};
+use sweep::Sweep;
+
+mod sweep;
+
+#[derive(Debug, Parser)]
+#[command(version, about = "Double pendulum simulation, without a window")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+fn main() -> std::process::ExitCode {
+    match Cli::parse().command.run() {
+        Ok(()) => std::process::ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            std::process::ExitCode::FAILURE
+        }
+    }
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Run the simulation without a window and write the trajectory out.
+    Simulate(Simulate),
+    /// Run the simulation over a grid of one or two parameters and tabulate how each run went.
+    Sweep(Sweep),
+}
+
+impl Command {
+    fn run(self) -> Result<(), String> {
+        match self {
+            Command::Simulate(simulate) => simulate.run(),
+            Command::Sweep(sweep) => sweep.run(),
+        }
+    }
+}
+
+/// The pendulum to simulate: a starting point, optionally overridden parameter by parameter.
+#[derive(Debug, Args)]
+struct Model {
+    /// Scenario file (.toml or .json) to start from.
+    #[arg(long, conflicts_with = "preset")]
+    scenario: Option<PathBuf>,
+    /// Built-in preset to start from, e.g. "chaotic-release".
+    #[arg(long)]
+    preset: Option<String>,
+    /// Number of arms in the chain.
+    #[arg(long)]
+    links: Option<usize>,
+    /// Gravitational acceleration in m/s².
+    #[arg(long)]
+    gravity: Option<f32>,
+    /// Quadratic air drag coefficient on the bobs, in kg/m.
+    #[arg(long)]
+    air_drag: Option<f32>,
+    /// Bob masses in kg, one per arm.
+    #[arg(long, value_delimiter = ',', allow_negative_numbers = true)]
+    masses: Vec<f32>,
+    /// Arm lengths in m, one per arm.
+    #[arg(long, value_delimiter = ',', allow_negative_numbers = true)]
+    lengths: Vec<f32>,
+    /// Joint friction in N·m·s, one per arm.
+    #[arg(long, value_delimiter = ',', allow_negative_numbers = true)]
+    damping: Vec<f32>,
+    /// Initial angles in radians from the downward vertical, one per arm.
+    #[arg(long, value_delimiter = ',', allow_negative_numbers = true)]
+    angles: Vec<f32>,
+    /// Initial angular velocities in rad/s, one per arm.
+    #[arg(long, value_delimiter = ',', allow_negative_numbers = true)]
+    velocities: Vec<f32>,
+    /// Integration scheme, e.g. runge_kutta4 or dormand_prince.
+    #[arg(long)]
+    integrator: Option<Method>,
+    /// Fixed time step in seconds.
+    #[arg(long)]
+    time_step: Option<f64>,
+}
+
+impl Model {
+    /// Builds the pendulum and the time step it should be advanced with.
+    fn build(&self) -> Result<(NPendulum, f64), String> {
+        let scenario = match (&self.scenario, &self.preset) {
+            (Some(path), _) => read_scenario(path)?,
+            (None, Some(name)) => find_preset(name)?,
+            (None, None) => Scenario::new(&NPendulum::default(), SimulationSettings::default()),
+        };
+        let mut pendulum = scenario.pendulum();
+
+        if let Some(links) = self.links {
+            if links == 0 {
+                return Err("a pendulum needs at least one link".to_string());
+            }
+            while pendulum.links() < links {
+                pendulum.add_link();
+            }
+            while pendulum.links() > links {
+                pendulum.remove_link();
+            }
+        }
+
+        let links = pendulum.links();
+        let per_link = |name: &str, values: &[f32]| -> Result<(), String> {
+            match values.len() {
+                0 => Ok(()),
+                len if len == links => Ok(()),
+                len => Err(format!("--{name} has {len} values for {links} links")),
+            }
+        };
+        per_link("masses", &self.masses)?;
+        per_link("lengths", &self.lengths)?;
+        per_link("damping", &self.damping)?;
+        per_link("angles", &self.angles)?;
+        per_link("velocities", &self.velocities)?;
+
+        if let Some(gravity) = self.gravity {
+            pendulum.gravity = gravity;
+        }
+        if let Some(air_drag) = self.air_drag {
+            pendulum.air_drag = air_drag;
+        }
+        if let Some(integrator) = self.integrator {
+            pendulum.integrator = integrator;
+        }
+        for (i, link) in pendulum.pendula.iter_mut().enumerate() {
+            if let Some(&mass) = self.masses.get(i) {
+                link.mass = mass;
+            }
+            if let Some(&length) = self.lengths.get(i) {
+                link.arm_length = length;
+            }
+            if let Some(&damping) = self.damping.get(i) {
+                link.joint_damping = damping;
+            }
+        }
+
+        let mut state = State {
+            angles: pendulum
+                .pendula
+                .iter()
+                .map(|p| p.unwrapped_angle())
+                .collect(),
+            velocities: pendulum.state().velocities,
+        };
+        if !self.angles.is_empty() {
+            state.angles.clone_from(&self.angles);
+        }
+        if !self.velocities.is_empty() {
+            state.velocities.clone_from(&self.velocities);
+        }
+        pendulum
+            .pendula
+            .iter_mut()
+            .for_each(Pendulum::reset_counters);
+        pendulum.set_state(&state);
+
+        // Kept in f64 so that output times are multiples of the step as given, e.g. 0.005 rather
+        // than 0.004999999888241291: a step read from a scenario is widened through its shortest
+        // decimal, not its exact binary value.
+        let time_step = self.time_step.unwrap_or_else(|| {
+            let time_step = scenario.simulation.time_step;
+            time_step
+                .to_string()
+                .parse()
+                .unwrap_or(f64::from(time_step))
+        });
+        let simulation = SimulationSettings {
+            time_step: time_step as f32,
+            ..scenario.simulation
+        };
+        validate(&pendulum, simulation)?;
+
+        Ok((pendulum, time_step))
+    }
+}
+
+#[derive(Debug, Args)]
+struct Simulate {
+    #[command(flatten)]
+    model: Model,
+    /// Simulated time in seconds.
+    #[arg(long, default_value_t = 10.0, conflicts_with = "steps")]
+    duration: f64,
+    /// Number of time steps, instead of a duration.
+    #[arg(long)]
+    steps: Option<u64>,
+    /// Write every n-th step only.
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+    every: u64,
+    /// Output format; guessed from the output file's extension when omitted, CSV otherwise.
+    #[arg(long)]
+    format: Option<OutputFormat>,
+    /// File to write to instead of standard output.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    Csv,
+    /// One JSON object per line.
+    Jsonl,
+}
+
+impl Simulate {
+    fn run(self) -> Result<(), String> {
+        let (mut pendulum, time_step) = self.model.build()?;
+        let steps = self
+            .steps
+            .unwrap_or_else(|| (self.duration / time_step).round() as u64);
+
+        let format = output_format(self.format, self.output.as_deref());
+        let mut out = create_output(self.output.as_deref())?;
+
+        // The trajectory up to a divergence is still written out before reporting it.
+        let mut diverged = None;
+        let result = (|| {
+            if format == OutputFormat::Csv {
+                writeln!(out, "{}", Sample::csv_header(pendulum.links()))?;
+            }
+            write_sample(&mut out, format, &Sample::new(0.0, &pendulum))?;
+            for step in 1..=steps {
+                if let Err(e) = pendulum.update(time_step as f32) {
+                    let time = round_time(step as f64 * time_step);
+                    diverged = Some(format!("at {time} s: {e}"));
+                    break;
+                }
+                if step % self.every == 0 {
+                    let time = step as f64 * time_step;
+                    write_sample(&mut out, format, &Sample::new(time, &pendulum))?;
+                }
+            }
+            out.flush()
+        })();
+
+        finish_output(result, "trajectory")?;
+        diverged.map_or(Ok(()), Err)
+    }
+}
+
+fn write_sample(out: &mut impl Write, format: OutputFormat, sample: &Sample) -> io::Result<()> {
+    match format {
+        OutputFormat::Csv => writeln!(out, "{}", sample.csv_row()),
+        OutputFormat::Jsonl => {
+            serde_json::to_writer(&mut *out, sample)?;
+            writeln!(out)
+        }
+    }
+}
+
+fn output_format(format: Option<OutputFormat>, output: Option<&Path>) -> OutputFormat {
+    format
+        .or_else(|| {
+            let extension = output?.extension()?.to_str()?;
+            OutputFormat::from_str(extension, true).ok()
+        })
+        .unwrap_or(OutputFormat::Csv)
+}
+
+fn finish_output(result: io::Result<()>, what: &str) -> Result<(), String> {
+    match result {
+        // The reader went away, e.g. when piped into `head`; that is not a failure.
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        result => result.map_err(|e| format!("could not write {what}: {e}")),
+    }
+}
+
+/// Checks that `pendulum` can be simulated with `simulation`, the way a scenario file is checked.
+fn validate(pendulum: &NPendulum, simulation: SimulationSettings) -> Result<(), String> {
+    match Scenario::new(pendulum, simulation).validate() {
+        Ok(()) => Ok(()),
+        Err(ScenarioError::Invalid(reason)) => Err(reason),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn read_scenario(path: &Path) -> Result<Scenario, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("could not read {}: {e}", path.display()))?;
+    Scenario::parse_file(&path.to_string_lossy(), &text)
+        .map_err(|e| format!("{}: {e}", path.display()))
+}
+
+/// Finds a preset by name, ignoring case and treating hyphens as spaces.
+fn find_preset(name: &str) -> Result<Scenario, String> {
+    let key = |name: &str| name.to_lowercase().replace('-', " ");
+    PRESETS
+        .iter()
+        .find(|preset| key(preset.name) == key(name))
+        .map(|preset| preset.scenario())
+        .ok_or_else(|| {
+            let names: Vec<_> = PRESETS
+                .iter()
+                .map(|preset| format!("\"{}\"", preset.name.to_lowercase().replace(' ', "-")))
+                .collect();
+            format!(
+                "unknown preset {name:?}; expected one of {}",
+                names.join(", ")
+            )
+        })
+}
+
+fn create_output(path: Option<&Path>) -> Result<Box<dyn Write>, String> {
+    Ok(match path {
+        Some(path) => {
+            Box::new(BufWriter::new(File::create(path).map_err(|e| {
+                format!("could not create {}: {e}", path.display())
+            })?))
+        }
+        None => Box::new(BufWriter::new(io::stdout().lock())),
+    })
+}
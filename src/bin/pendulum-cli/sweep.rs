@@ -0,0 +1,153 @@
+//! The `sweep` subcommand: runs a model over a grid of one or two parameters in parallel and
+//! tabulates how each run went.
+
+use std::{io::Write, path::PathBuf};
+
+use clap::Args;
+use double_pendulum::{
+    parallel::{self, ParallelMap},
+    pendulum::NPendulum,
+    scenario::SimulationSettings,
+    sweep::{Axis, Summary},
+};
+
+use crate::{create_output, finish_output, output_format, validate, Model, OutputFormat};
+
+#[derive(Debug, Args)]
+pub struct Sweep {
+    /// Pendulum every run starts from, before the swept parameters are applied.
+    #[command(flatten)]
+    model: Model,
+    /// First swept parameter as parameter=start:end:count, e.g. mass_ratio=0.1:10:50. Parameters
+    /// are mass_ratio, length_ratio, gravity, angle1, angle2 and damping.
+    #[arg(long)]
+    x: Axis,
+    /// Optional second swept parameter, in the same form.
+    #[arg(long)]
+    y: Option<Axis>,
+    /// Simulated time of every run, in seconds.
+    #[arg(long, default_value_t = 10.0)]
+    duration: f64,
+    /// Number of runs in parallel; defaults to the number of CPUs.
+    #[arg(short, long)]
+    jobs: Option<usize>,
+    /// Output format; guessed from the output file's extension when omitted, CSV otherwise.
+    #[arg(long)]
+    format: Option<OutputFormat>,
+    /// File to write to instead of standard output.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+impl Sweep {
+    pub fn run(self) -> Result<(), String> {
+        let (pendulum, time_step) = self.model.build()?;
+        let steps = (self.duration / time_step).round() as u64;
+
+        let axes: Vec<Axis> = std::iter::once(self.x).chain(self.y).collect();
+        for axis in &axes {
+            if pendulum.links() < axis.parameter.min_links() {
+                return Err(format!(
+                    "{} needs at least {} links",
+                    axis.parameter.key(),
+                    axis.parameter.min_links()
+                ));
+            }
+        }
+
+        // Grid points in row-major order, the first axis varying fastest.
+        let points: Vec<Vec<f32>> = match self.y {
+            None => self.x.values().map(|x| vec![x]).collect(),
+            Some(y) => y
+                .values()
+                .flat_map(|y| self.x.values().map(move |x| vec![x, y]))
+                .collect(),
+        };
+
+        // Check every grid point up front, rather than tabulate runs of an invalid model.
+        let simulation = SimulationSettings {
+            time_step: time_step as f32,
+            ..SimulationSettings::default()
+        };
+        for values in &points {
+            validate(&configure(&pendulum, &axes, values), simulation).map_err(|reason| {
+                let point: Vec<_> = axes
+                    .iter()
+                    .zip(values)
+                    .map(|(axis, value)| format!("{}={value}", axis.parameter.key()))
+                    .collect();
+                format!("at {}: {reason}", point.join(", "))
+            })?;
+        }
+
+        let jobs = self.jobs.unwrap_or_else(parallel::default_jobs);
+        let summaries = {
+            let (axes, points) = (axes.clone(), points.clone());
+            ParallelMap::spawn(points.len(), jobs, move |index| {
+                let pendulum = configure(&pendulum, &axes, &points[index]);
+                Summary::of_run(pendulum, time_step, steps)
+            })
+            .wait()
+        };
+
+        let format = output_format(self.format, self.output.as_deref());
+        let mut out = create_output(self.output.as_deref())?;
+        let result = (|| {
+            if format == OutputFormat::Csv {
+                let keys: Vec<_> = axes.iter().map(|axis| axis.parameter.key()).collect();
+                writeln!(out, "{},{}", keys.join(","), Summary::CSV_HEADER)?;
+            }
+            for (values, summary) in points.iter().zip(&summaries) {
+                match format {
+                    OutputFormat::Csv => {
+                        let values: Vec<_> = values.iter().map(f32::to_string).collect();
+                        writeln!(out, "{},{}", values.join(","), summary.csv_row())?;
+                    }
+                    OutputFormat::Jsonl => {
+                        let row = SweepRow {
+                            parameters: Parameters(
+                                axes.iter()
+                                    .zip(values)
+                                    .map(|(axis, &value)| (axis.parameter.key(), value))
+                                    .collect(),
+                            ),
+                            summary,
+                        };
+                        serde_json::to_writer(&mut out, &row)?;
+                        writeln!(out)?;
+                    }
+                }
+            }
+            out.flush()
+        })();
+
+        finish_output(result, "sweep results")
+    }
+}
+
+/// A line of JSON sweep output: the swept parameter values, then the summary of the run.
+#[derive(serde::Serialize)]
+struct SweepRow<'a> {
+    #[serde(flatten)]
+    parameters: Parameters,
+    #[serde(flatten)]
+    summary: &'a Summary,
+}
+
+/// Parameter values keyed by name, serialised as a map in the order of the axes.
+struct Parameters(Vec<(&'static str, f32)>);
+
+impl serde::Serialize for Parameters {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.0.iter().copied())
+    }
+}
+
+/// `pendulum` with the swept parameters set to `values`, one per axis.
+fn configure(pendulum: &NPendulum, axes: &[Axis], values: &[f32]) -> NPendulum {
+    let mut pendulum = pendulum.clone();
+    for (axis, &value) in axes.iter().zip(values) {
+        axis.parameter.apply(&mut pendulum, value);
+    }
+    pendulum
+}
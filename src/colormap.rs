@@ -0,0 +1,22 @@
+//! Colour scales for the plots drawn as images.
+
+use eframe::egui;
+
+/// Viridis-like colour scale from yellow (`s = 0`) to purple (`s = 1`).
+pub fn gradient(s: f32) -> egui::Color32 {
+    const STOPS: [[u8; 3]; 5] = [
+        [253, 231, 37],
+        [94, 201, 98],
+        [33, 145, 140],
+        [59, 82, 139],
+        [68, 1, 84],
+    ];
+
+    let s = s.clamp(0.0, 1.0) * (STOPS.len() - 1) as f32;
+    let i = (s as usize).min(STOPS.len() - 2);
+    let t = s - i as f32;
+    let [r, g, b] = std::array::from_fn(|c| {
+        egui::lerp(STOPS[i][c] as f32..=STOPS[i + 1][c] as f32, t).round() as u8
+    });
+    egui::Color32::from_rgb(r, g, b)
+}
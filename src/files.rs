@@ -0,0 +1,104 @@
+//! Saving and opening text files: native file dialogs on desktop, downloads and uploads in the
+//! browser.
+
+use std::sync::mpsc::Sender;
+
+/// A file type the dialogs offer, by name and extensions.
+pub type Filter = (&'static str, &'static [&'static str]);
+
+/// A text file picked by the user.
+pub struct OpenedFile {
+    pub name: String,
+    pub contents: String,
+}
+
+/// Asks where to save a file and writes it. `contents` is given the chosen file name, so it can
+/// pick a format from the extension. Cancelling the dialog is not an error.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save(
+    file_name: &str,
+    filter: Filter,
+    contents: impl FnOnce(&str) -> Result<String, String>,
+) -> Result<(), String> {
+    let Some(path) = rfd::FileDialog::new()
+        .set_file_name(file_name)
+        .add_filter(filter.0, filter.1)
+        .save_file()
+    else {
+        return Ok(());
+    };
+
+    let contents = contents(&path.to_string_lossy())?;
+    std::fs::write(&path, contents).map_err(|e| format!("could not write {}: {e}", path.display()))
+}
+
+/// Offers the file as a download named `file_name`.
+#[cfg(target_arch = "wasm32")]
+pub fn save(
+    file_name: &str,
+    _filter: Filter,
+    contents: impl FnOnce(&str) -> Result<String, String>,
+) -> Result<(), String> {
+    let contents = contents(file_name)?;
+    download(file_name, &contents).map_err(|e| format!("could not download {file_name}: {e:?}"))
+}
+
+#[cfg(target_arch = "wasm32")]
+fn download(file_name: &str, contents: &str) -> Result<(), wasm_bindgen::JsValue> {
+    use wasm_bindgen::{JsCast, JsValue};
+
+    let parts = js_sys::Array::of1(&JsValue::from_str(contents));
+    let mut options = web_sys::BlobPropertyBag::new();
+    options.type_("text/plain");
+    let blob = web_sys::Blob::new_with_str_sequence_and_options(&parts, &options)?;
+    let url = web_sys::Url::create_object_url_with_blob(&blob)?;
+
+    let document = web_sys::window()
+        .and_then(|window| window.document())
+        .ok_or_else(|| JsValue::from_str("no document"))?;
+    let anchor: web_sys::HtmlAnchorElement = document.create_element("a")?.dyn_into()?;
+    anchor.set_href(&url);
+    anchor.set_download(file_name);
+    anchor.click();
+
+    web_sys::Url::revoke_object_url(&url)
+}
+
+/// Asks for a file to open and sends its contents through `sender` once read. Cancelling the
+/// dialog sends nothing.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn open(filter: Filter, sender: Sender<Result<OpenedFile, String>>) {
+    let Some(path) = rfd::FileDialog::new()
+        .add_filter(filter.0, filter.1)
+        .pick_file()
+    else {
+        return;
+    };
+
+    let file = std::fs::read_to_string(&path)
+        .map(|contents| OpenedFile {
+            name: path.to_string_lossy().into_owned(),
+            contents,
+        })
+        .map_err(|e| format!("could not read {}: {e}", path.display()));
+    sender.send(file).ok();
+}
+
+/// Asks for a file to upload; its contents arrive through `sender` on a later frame.
+#[cfg(target_arch = "wasm32")]
+pub fn open(filter: Filter, sender: Sender<Result<OpenedFile, String>>) {
+    let dialog = rfd::AsyncFileDialog::new().add_filter(filter.0, filter.1);
+
+    wasm_bindgen_futures::spawn_local(async move {
+        if let Some(handle) = dialog.pick_file().await {
+            let name = handle.file_name();
+            let file = String::from_utf8(handle.read().await)
+                .map(|contents| OpenedFile {
+                    name: name.clone(),
+                    contents,
+                })
+                .map_err(|e| format!("{name} is not a text file: {e}"));
+            sender.send(file).ok();
+        }
+    });
+}
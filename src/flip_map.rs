@@ -0,0 +1,299 @@
+//! The time-to-first-flip map: every pixel is a pair of initial angles, coloured by how long the
+//! pendulum released from there takes to flip an arm over the top.
+
+use std::f64::consts::PI;
+
+use crate::colormap::gradient;
+use double_pendulum::{
+    fractal::{self, Grid},
+    parallel::{self, ParallelMap},
+    pendulum::NPendulum,
+};
+use eframe::egui;
+
+/// Time to the first flip of a pixel once computed, `None` if no arm flipped in time.
+type FlipTime = Option<f32>;
+
+pub struct FlipMap {
+    /// Pixels across the rendered view.
+    pub resolution: usize,
+    /// Simulated seconds after which a start counts as never flipping.
+    pub max_time: f32,
+    render: Option<Render>,
+    texture: Option<egui::TextureHandle>,
+    /// Visible region of the map as of the last frame.
+    view: Option<egui_plot::PlotBounds>,
+    reset_view: bool,
+}
+
+impl Default for FlipMap {
+    fn default() -> Self {
+        Self {
+            resolution: 128,
+            max_time: 10.0,
+            render: None,
+            texture: None,
+            view: None,
+            reset_view: true,
+        }
+    }
+}
+
+impl FlipMap {
+    /// Shows the map and its controls, returning the initial angles the user clicked, if any.
+    pub fn ui(&mut self, ui: &mut egui::Ui, model: &NPendulum, time_step: f32) -> Option<[f32; 2]> {
+        if model.links() < 2 {
+            ui.centered_and_justified(|ui| ui.label("The flip map needs at least two links."));
+            return None;
+        }
+
+        if self.render.is_none() {
+            self.start(model, time_step);
+        }
+        self.poll(ui.ctx());
+
+        ui.horizontal_wrapped(|ui| {
+            ui.label("Resolution:");
+            egui::ComboBox::from_id_source("flip_map_resolution")
+                .selected_text(self.resolution.to_string())
+                .show_ui(ui, |ui| {
+                    for resolution in [64, 128, 256, 512] {
+                        ui.selectable_value(
+                            &mut self.resolution,
+                            resolution,
+                            resolution.to_string(),
+                        );
+                    }
+                });
+            ui.label("Max time:");
+            ui.add(egui::Slider::new(&mut self.max_time, 1.0..=60.0).suffix(" s"));
+
+            if ui.button("Render view").clicked() {
+                self.start(model, time_step);
+            }
+            if ui.button("Reset view").clicked() {
+                self.reset_view = true;
+                self.view = None;
+                self.start(model, time_step);
+            }
+
+            if let Some(render) = &self.render {
+                let progress = render.done as f32 / render.times.len() as f32;
+                ui.add(
+                    egui::ProgressBar::new(progress)
+                        .desired_width(120.0)
+                        .show_percentage(),
+                );
+                if !same_parameters(&render.model, model) {
+                    ui.colored_label(
+                        ui.visuals().warn_fg_color,
+                        "Parameters changed since this render",
+                    );
+                }
+            }
+        });
+        self.legend(ui);
+
+        let response = egui_plot::Plot::new("flip_map")
+            .data_aspect(1.0)
+            .allow_double_click_reset(false)
+            .x_axis_label("θ₁")
+            .y_axis_label("θ₂")
+            .x_axis_formatter(|value, _, _| format!("{:.2}π", value / PI))
+            .y_axis_formatter(|value, _, _| format!("{:.2}π", value / PI))
+            .show(ui, |plot_ui| {
+                if std::mem::take(&mut self.reset_view) {
+                    plot_ui
+                        .set_plot_bounds(egui_plot::PlotBounds::from_min_max([-PI, -PI], [PI, PI]));
+                }
+
+                if let (Some(render), Some(texture)) = (&self.render, &self.texture) {
+                    let grid = render.grid;
+                    let [width, height] = grid.size();
+                    let center = egui_plot::PlotPoint::new(
+                        grid.min[0] + 0.5 * width,
+                        grid.min[1] + 0.5 * height,
+                    );
+                    plot_ui.image(egui_plot::PlotImage::new(
+                        texture,
+                        center,
+                        egui::vec2(width, height),
+                    ));
+                }
+
+                plot_ui.pointer_coordinate()
+            });
+
+        self.view = Some(*response.transform.bounds());
+        let pointer = response.inner?;
+        response
+            .response
+            .clicked()
+            .then_some([pointer.x as f32, pointer.y as f32])
+    }
+
+    /// Starts rendering the visible region, abandoning any render in progress.
+    fn start(&mut self, model: &NPendulum, time_step: f32) {
+        let (min, max) = match &self.view {
+            Some(view) => (view.min(), view.max()),
+            None => ([-PI, -PI], [PI, PI]),
+        };
+        let aspect = (max[1] - min[1]) / (max[0] - min[0]);
+        let grid = Grid {
+            min: [min[0] as f32, min[1] as f32],
+            max: [max[0] as f32, max[1] as f32],
+            width: self.resolution,
+            height: ((self.resolution as f64 * aspect).round() as usize)
+                .clamp(1, 4 * self.resolution),
+        };
+
+        self.render = Some(Render::new(grid, model.clone(), time_step, self.max_time));
+    }
+
+    /// Collects newly computed pixels and updates the texture.
+    fn poll(&mut self, ctx: &egui::Context) {
+        let Some(render) = &mut self.render else {
+            return;
+        };
+
+        let changed = render.poll();
+        if render.done < render.times.len() {
+            ctx.request_repaint();
+        }
+
+        if changed || self.texture.is_none() {
+            let image = render.image();
+            match &mut self.texture {
+                Some(texture) => texture.set(image, egui::TextureOptions::NEAREST),
+                None => {
+                    self.texture =
+                        Some(ctx.load_texture("flip_map", image, egui::TextureOptions::NEAREST))
+                }
+            }
+        }
+    }
+
+    fn legend(&self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("First flip after:");
+            ui.label("0 s");
+
+            let (rect, _) = ui.allocate_exact_size(egui::vec2(200.0, 12.0), egui::Sense::hover());
+            let mut mesh = egui::Mesh::default();
+            const STOPS: usize = 32;
+            for i in 0..=STOPS {
+                let s = i as f32 / STOPS as f32;
+                let x = egui::lerp(rect.x_range(), s);
+                let colour = gradient(s);
+                mesh.colored_vertex(egui::pos2(x, rect.top()), colour);
+                mesh.colored_vertex(egui::pos2(x, rect.bottom()), colour);
+                if i > 0 {
+                    let base = 2 * (i as u32 - 1);
+                    mesh.add_triangle(base, base + 1, base + 2);
+                    mesh.add_triangle(base + 1, base + 2, base + 3);
+                }
+            }
+            ui.painter().add(mesh);
+
+            ui.label(format!("{} s (log scale)", self.max_time));
+            let (swatch, _) = ui.allocate_exact_size(egui::vec2(12.0, 12.0), egui::Sense::hover());
+            ui.painter().rect_filled(swatch, 0.0, egui::Color32::BLACK);
+            ui.label("no flip");
+        });
+    }
+}
+
+/// A map being computed, pixel by pixel.
+struct Render {
+    grid: Grid,
+    model: NPendulum,
+    max_time: f32,
+    /// Pixels in row-major order, `None` until computed.
+    times: Vec<Option<FlipTime>>,
+    done: usize,
+    /// Rows of the map, abandoned when the render is.
+    rows: ParallelMap<Vec<FlipTime>>,
+}
+
+impl Render {
+    fn new(grid: Grid, model: NPendulum, time_step: f32, max_time: f32) -> Self {
+        Self {
+            rows: {
+                let model = model.clone();
+                ParallelMap::spawn(grid.height, parallel::default_jobs(), move |row| {
+                    compute_row(&grid, &model, time_step, max_time, row)
+                })
+            },
+            times: vec![None; grid.width * grid.height],
+            done: 0,
+            grid,
+            model,
+            max_time,
+        }
+    }
+
+    /// Seconds of each frame spent computing rows where they cannot be left to other threads.
+    #[cfg(target_arch = "wasm32")]
+    const BUDGET: f64 = 0.01;
+
+    /// Whether any pixels were computed since the last call.
+    fn poll(&mut self) -> bool {
+        #[cfg(target_arch = "wasm32")]
+        let keep_going = {
+            let start = eframe::web::now_sec();
+            move || eframe::web::now_sec() - start < Self::BUDGET
+        };
+        #[cfg(not(target_arch = "wasm32"))]
+        let keep_going = || true;
+
+        let done = self.done;
+        for (row, times) in self.rows.finished(keep_going) {
+            let start = row * self.grid.width;
+            for (pixel, time) in self.times[start..start + times.len()].iter_mut().zip(times) {
+                *pixel = Some(time);
+            }
+            self.done += self.grid.width;
+        }
+        self.done != done
+    }
+
+    fn image(&self) -> egui::ColorImage {
+        egui::ColorImage {
+            size: [self.grid.width, self.grid.height],
+            pixels: self
+                .times
+                .iter()
+                .map(|time| match time {
+                    None => egui::Color32::TRANSPARENT,
+                    Some(None) => egui::Color32::BLACK,
+                    Some(Some(time)) => gradient((1.0 + time).ln() / (1.0 + self.max_time).ln()),
+                })
+                .collect(),
+        }
+    }
+}
+
+fn compute_row(
+    grid: &Grid,
+    model: &NPendulum,
+    time_step: f32,
+    max_time: f32,
+    row: usize,
+) -> Vec<FlipTime> {
+    (0..grid.width)
+        .map(|column| {
+            fractal::first_flip_time(model, grid.angles(column, row), time_step, max_time)
+        })
+        .collect()
+}
+
+/// Whether two pendulums have the same physical parameters, whatever their state.
+fn same_parameters(a: &NPendulum, b: &NPendulum) -> bool {
+    a.gravity == b.gravity
+        && a.air_drag == b.air_drag
+        && a.integrator.same_kind(&b.integrator)
+        && a.links() == b.links()
+        && a.pendula.iter().zip(&b.pendula).all(|(a, b)| {
+            a.mass == b.mass && a.arm_length == b.arm_length && a.joint_damping == b.joint_damping
+        })
+}
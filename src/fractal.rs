@@ -0,0 +1,93 @@
+use crate::pendulum::{NPendulum, State};
+
+/// A raster over initial angles of the first two arms, released from rest: columns sweep `θ1`
+/// from left to right, rows sweep `θ2` from top to bottom.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Grid {
+    /// Lowest `θ1` and `θ2` covered, in radians.
+    pub min: [f32; 2],
+    /// Highest `θ1` and `θ2` covered, in radians.
+    pub max: [f32; 2],
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Grid {
+    /// Initial angles at the centre of a pixel.
+    pub fn angles(&self, column: usize, row: usize) -> [f32; 2] {
+        let [width, height] = self.size();
+        [
+            self.min[0] + (column as f32 + 0.5) * width / self.width as f32,
+            self.max[1] - (row as f32 + 0.5) * height / self.height as f32,
+        ]
+    }
+
+    /// Angular extent in radians along each axis.
+    pub fn size(&self) -> [f32; 2] {
+        [self.max[0] - self.min[0], self.max[1] - self.min[1]]
+    }
+}
+
+/// Time until any arm of `model` first flips after being released from rest with the first two
+/// arms at `angles` and any others hanging down, or `None` if none flips within `max_time`.
+pub fn first_flip_time(
+    model: &NPendulum,
+    angles: [f32; 2],
+    time_step: f32,
+    max_time: f32,
+) -> Option<f32> {
+    let mut pendulum = model.clone();
+    let mut state = State::zeros(pendulum.links());
+    state.angles[..2].copy_from_slice(&angles);
+    pendulum.set_state(&state);
+
+    // Friction only takes energy away, so starts below the flip energy can be skipped.
+    if pendulum.potential_energy() < pendulum.flip_energy() {
+        return None;
+    }
+
+    pendulum.pendula.iter_mut().for_each(|p| p.reset_counters());
+    let steps = (max_time / time_step).ceil() as u32;
+    for step in 1..=steps {
+        // A start that blows up before flipping is reported as not flipping.
+        pendulum.update(time_step).ok()?;
+        if pendulum.pendula.iter().any(|p| p.flips > 0) {
+            return Some(step as f32 * time_step);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn low_start_never_flips() {
+        let model = NPendulum::new(2);
+        assert_eq!(first_flip_time(&model, [0.5, -0.5], 0.01, 30.0), None);
+        assert_eq!(first_flip_time(&model, [0.0, 0.0], 0.01, 30.0), None);
+    }
+
+    #[test]
+    fn high_start_flips_soon() {
+        let model = NPendulum::new(2);
+        // Upper arm nearly upright and the lower one well out: an arm goes over the top within
+        // the first second, and not at all when the run is cut short before then.
+        let time = first_flip_time(&model, [-3.0, 2.0], 0.01, 30.0).unwrap();
+        assert!(time > 0.0 && time < 1.0, "first flip after {time} s");
+        assert_eq!(first_flip_time(&model, [-3.0, 2.0], 0.01, 0.5 * time), None);
+    }
+
+    #[test]
+    fn grid_angles_are_pixel_centres() {
+        let grid = Grid {
+            min: [-2.0, -1.0],
+            max: [2.0, 1.0],
+            width: 4,
+            height: 2,
+        };
+        assert_eq!(grid.angles(0, 0), [-1.5, 0.5]);
+        assert_eq!(grid.angles(3, 1), [1.5, -0.5]);
+    }
+}
@@ -0,0 +1,76 @@
+use std::collections::VecDeque;
+
+/// Time series of samples, bounded both in length and in age.
+///
+/// Mirrors the interface of `egui::util::History` so the physics can be recorded without
+/// depending on a GUI.
+#[derive(Debug, Clone, PartialEq)]
+pub struct History<T> {
+    min_len: usize,
+    max_len: usize,
+    max_age: f32,
+    values: VecDeque<(f64, T)>,
+}
+
+impl<T> History<T> {
+    /// Keeps at least `length_range.start` and fewer than `length_range.end` samples, dropping
+    /// samples older than `max_age` seconds whenever there are more than the minimum.
+    pub fn new(length_range: std::ops::Range<usize>, max_age: f32) -> Self {
+        Self {
+            min_len: length_range.start,
+            max_len: length_range.end,
+            max_age,
+            values: VecDeque::with_capacity(length_range.end),
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.values.clear();
+    }
+
+    /// Records `value` at time `now`, which should not be earlier than the previous sample.
+    pub fn add(&mut self, now: f64, value: T) {
+        self.values.push_back((now, value));
+        self.flush(now);
+    }
+
+    /// Drops samples beyond the length and age limits.
+    pub fn flush(&mut self, now: f64) {
+        while self.values.len() > self.max_len {
+            self.values.pop_front();
+        }
+
+        while self.values.len() > self.min_len {
+            match self.values.front() {
+                Some((time, _)) if *time < now - self.max_age as f64 => self.values.pop_front(),
+                _ => break,
+            };
+        }
+    }
+
+    /// Drops the samples recorded after `time`, for when the past is rewritten from then on.
+    pub fn truncate_after(&mut self, time: f64) {
+        let kept = self.values.partition_point(|(t, _)| *t <= time);
+        self.values.truncate(kept);
+    }
+
+    /// Time of the most recent sample.
+    pub fn latest_time(&self) -> Option<f64> {
+        self.values.back().map(|(time, _)| *time)
+    }
+
+    /// Iterates over `(time, value)` pairs, oldest first.
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = (f64, &T)> + '_ {
+        self.values.iter().map(|(time, value)| (*time, value))
+    }
+}
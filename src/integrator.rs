@@ -0,0 +1,768 @@
+use crate::pendulum::{add_scaled, NPendulum, State};
+use serde::{Deserialize, Serialize};
+
+/// A numerical scheme advancing an [`NPendulum`] through phase space.
+pub trait Integrator {
+    fn step(&mut self, system: &NPendulum, state: &State, delta_time: f32) -> State;
+}
+
+/// First-order Euler step using the derivative at the start of the step.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct ExplicitEuler;
+
+impl Integrator for ExplicitEuler {
+    fn step(&mut self, system: &NPendulum, state: &State, delta_time: f32) -> State {
+        state.add_scaled(&system.derivative(state), delta_time)
+    }
+}
+
+/// Euler step which advances the velocities first and moves the angles with the new velocities.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct SemiImplicitEuler;
+
+impl Integrator for SemiImplicitEuler {
+    fn step(&mut self, system: &NPendulum, state: &State, delta_time: f32) -> State {
+        let velocities = add_scaled(&state.velocities, &system.accelerations(state), delta_time);
+
+        State {
+            angles: add_scaled(&state.angles, &velocities, delta_time),
+            velocities,
+        }
+    }
+}
+
+/// Classic fourth-order Runge-Kutta.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct RungeKutta4;
+
+impl Integrator for RungeKutta4 {
+    fn step(&mut self, system: &NPendulum, state: &State, delta_time: f32) -> State {
+        let h = delta_time;
+
+        let k1 = system.derivative(state);
+        let k2 = system.derivative(&state.add_scaled(&k1, 0.5 * h));
+        let k3 = system.derivative(&state.add_scaled(&k2, 0.5 * h));
+        let k4 = system.derivative(&state.add_scaled(&k3, h));
+
+        state
+            .add_scaled(&k1, h / 6.0)
+            .add_scaled(&k2, h / 3.0)
+            .add_scaled(&k3, h / 3.0)
+            .add_scaled(&k4, h / 6.0)
+    }
+}
+
+/// Generalised Störmer-Verlet in canonical coordinates, in its velocity Verlet form: half a kick,
+/// a full drift, half a kick.
+///
+/// The Hamiltonian of the pendulum is not separable, so the first kick and the drift are implicit
+/// and solved by fixed-point iteration. Friction enters the kicks as a generalised force, which
+/// of course breaks symplecticity.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VelocityVerlet {
+    /// Implicit stages left unconverged during the last call to [`Integrator::step`].
+    #[serde(skip)]
+    pub unconverged_stages: u32,
+}
+
+impl VelocityVerlet {
+    pub const DEFAULT: Self = Self {
+        unconverged_stages: 0,
+    };
+}
+
+impl Integrator for VelocityVerlet {
+    fn step(&mut self, system: &NPendulum, state: &State, delta_time: f32) -> State {
+        let (state, unconverged) = Canonical::step(system, state, delta_time, verlet);
+        self.unconverged_stages = unconverged;
+        state
+    }
+}
+
+fn verlet(system: &NPendulum, q0: &[f64], p0: &[f64], delta_time: f64) -> Solution {
+    let h = 0.5 * delta_time;
+    let mut converged = true;
+
+    let p = fixed_point(p0.to_vec(), |p| add_scaled(p0, &force(system, q0, p), h)).unwrap_or_else(
+        |p| {
+            converged = false;
+            p
+        },
+    );
+    let v0 = system.velocities_from_momenta(q0, &p);
+    let q1 = fixed_point(q0.to_vec(), |q| {
+        add_scaled(
+            &add_scaled(q0, &v0, h),
+            &system.velocities_from_momenta(q, &p),
+            h,
+        )
+    })
+    .unwrap_or_else(|q| {
+        converged = false;
+        q
+    });
+    let p1 = add_scaled(&p, &force(system, &q1, &p), h);
+
+    let canonical = Canonical { q: q1, p: p1 };
+    if converged {
+        Ok(canonical)
+    } else {
+        Err(canonical)
+    }
+}
+
+/// Generalised Störmer-Verlet in canonical coordinates, in its leapfrog (position Verlet) form:
+/// half a drift, a full kick, half a drift.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Leapfrog {
+    /// Implicit stages left unconverged during the last call to [`Integrator::step`].
+    #[serde(skip)]
+    pub unconverged_stages: u32,
+}
+
+impl Leapfrog {
+    pub const DEFAULT: Self = Self {
+        unconverged_stages: 0,
+    };
+}
+
+impl Integrator for Leapfrog {
+    fn step(&mut self, system: &NPendulum, state: &State, delta_time: f32) -> State {
+        let (state, unconverged) = Canonical::step(system, state, delta_time, leapfrog);
+        self.unconverged_stages = unconverged;
+        state
+    }
+}
+
+fn leapfrog(system: &NPendulum, q0: &[f64], p0: &[f64], delta_time: f64) -> Solution {
+    let h = 0.5 * delta_time;
+    let mut converged = true;
+
+    let q = fixed_point(q0.to_vec(), |q| {
+        add_scaled(q0, &system.velocities_from_momenta(q, p0), h)
+    })
+    .unwrap_or_else(|q| {
+        converged = false;
+        q
+    });
+    let f0 = force(system, &q, p0);
+    let p1 = fixed_point(p0.to_vec(), |p| {
+        add_scaled(&add_scaled(p0, &f0, h), &force(system, &q, p), h)
+    })
+    .unwrap_or_else(|p| {
+        converged = false;
+        p
+    });
+    let q1 = add_scaled(&q, &system.velocities_from_momenta(&q, &p1), h);
+
+    let canonical = Canonical { q: q1, p: p1 };
+    if converged {
+        Ok(canonical)
+    } else {
+        Err(canonical)
+    }
+}
+
+/// Yoshida's fourth-order symplectic scheme, composing three velocity Verlet steps.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Yoshida4 {
+    /// Implicit stages left unconverged during the last call to [`Integrator::step`].
+    #[serde(skip)]
+    pub unconverged_stages: u32,
+}
+
+impl Yoshida4 {
+    pub const DEFAULT: Self = Self {
+        unconverged_stages: 0,
+    };
+}
+
+impl Integrator for Yoshida4 {
+    fn step(&mut self, system: &NPendulum, state: &State, delta_time: f32) -> State {
+        let (state, unconverged) = Canonical::step(system, state, delta_time, yoshida);
+        self.unconverged_stages = unconverged;
+        state
+    }
+}
+
+fn yoshida(system: &NPendulum, q0: &[f64], p0: &[f64], delta_time: f64) -> Solution {
+    let cbrt2 = 2.0_f64.cbrt();
+    let w1 = 1.0 / (2.0 - cbrt2);
+    let w0 = -cbrt2 * w1;
+
+    let mut converged = true;
+    let mut canonical = Canonical {
+        q: q0.to_vec(),
+        p: p0.to_vec(),
+    };
+    for weight in [w1, w0, w1] {
+        canonical = verlet(system, &canonical.q, &canonical.p, weight * delta_time).unwrap_or_else(
+            |canonical| {
+                converged = false;
+                canonical
+            },
+        );
+    }
+
+    if converged {
+        Ok(canonical)
+    } else {
+        Err(canonical)
+    }
+}
+
+/// Adaptive Dormand-Prince 5(4) scheme, subdividing each requested step into as many internal
+/// steps as are needed to keep the local error estimate within tolerance.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DormandPrince {
+    #[serde(serialize_with = "crate::scenario::serialize_decimal")]
+    pub absolute_tolerance: f32,
+    #[serde(serialize_with = "crate::scenario::serialize_decimal")]
+    pub relative_tolerance: f32,
+    /// Internal steps accepted during the last call to [`Integrator::step`].
+    #[serde(skip)]
+    pub accepted_steps: u32,
+    /// Internal steps rejected during the last call to [`Integrator::step`].
+    #[serde(skip)]
+    pub rejected_steps: u32,
+    /// Internal steps of the last call to [`Integrator::step`] accepted outside the tolerance,
+    /// because they were already as small as allowed or too many had been tried.
+    #[serde(skip)]
+    pub forced_steps: u32,
+    /// Step size carried over as the first guess for the next call.
+    #[serde(skip)]
+    step_size: f32,
+}
+
+impl Default for DormandPrince {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+impl DormandPrince {
+    pub const DEFAULT: Self = Self::new(1e-5, 1e-5);
+
+    /// Smallest internal step, as a fraction of the requested step.
+    const MIN_STEP_FRACTION: f32 = 1e-4;
+    /// Internal steps tried before every further finite one is accepted, whatever its error.
+    const MAX_STEPS: u32 = 50_000;
+
+    pub const fn new(absolute_tolerance: f32, relative_tolerance: f32) -> Self {
+        Self {
+            absolute_tolerance,
+            relative_tolerance,
+            accepted_steps: 0,
+            rejected_steps: 0,
+            forced_steps: 0,
+            step_size: 0.0,
+        }
+    }
+
+    /// Scaled RMS norm of the error estimate; values at or below 1 are within tolerance.
+    fn error_norm(&self, error: &State, old: &State, new: &State) -> f32 {
+        let sum: f32 = error
+            .iter()
+            .zip(old.iter().zip(new.iter()))
+            .map(|(e, (y0, y1))| {
+                let scale =
+                    self.absolute_tolerance + self.relative_tolerance * y0.abs().max(y1.abs());
+                (e / scale).powi(2)
+            })
+            .sum();
+
+        (sum / error.iter().count() as f32).sqrt()
+    }
+}
+
+impl Integrator for DormandPrince {
+    fn step(&mut self, system: &NPendulum, state: &State, delta_time: f32) -> State {
+        let min_step = delta_time * Self::MIN_STEP_FRACTION;
+
+        let mut time = 0.0;
+        let mut y = state.clone();
+        let mut h = if self.step_size > 0.0 {
+            self.step_size.min(delta_time)
+        } else {
+            delta_time
+        };
+
+        self.accepted_steps = 0;
+        self.rejected_steps = 0;
+        self.forced_steps = 0;
+
+        let mut k1 = system.derivative(&y);
+        while time < delta_time {
+            let remaining = delta_time - time;
+            let h_try = h.min(remaining);
+
+            let k2 = system.derivative(&y.add_scaled(&k1, h_try / 5.0));
+            let k3 = system.derivative(
+                &y.add_scaled(&k1, h_try * 3.0 / 40.0)
+                    .add_scaled(&k2, h_try * 9.0 / 40.0),
+            );
+            let k4 = system.derivative(
+                &y.add_scaled(&k1, h_try * 44.0 / 45.0)
+                    .add_scaled(&k2, h_try * -56.0 / 15.0)
+                    .add_scaled(&k3, h_try * 32.0 / 9.0),
+            );
+            let k5 = system.derivative(
+                &y.add_scaled(&k1, h_try * 19372.0 / 6561.0)
+                    .add_scaled(&k2, h_try * -25360.0 / 2187.0)
+                    .add_scaled(&k3, h_try * 64448.0 / 6561.0)
+                    .add_scaled(&k4, h_try * -212.0 / 729.0),
+            );
+            let k6 = system.derivative(
+                &y.add_scaled(&k1, h_try * 9017.0 / 3168.0)
+                    .add_scaled(&k2, h_try * -355.0 / 33.0)
+                    .add_scaled(&k3, h_try * 46732.0 / 5247.0)
+                    .add_scaled(&k4, h_try * 49.0 / 176.0)
+                    .add_scaled(&k5, h_try * -5103.0 / 18656.0),
+            );
+            let y_new = y
+                .add_scaled(&k1, h_try * 35.0 / 384.0)
+                .add_scaled(&k3, h_try * 500.0 / 1113.0)
+                .add_scaled(&k4, h_try * 125.0 / 192.0)
+                .add_scaled(&k5, h_try * -2187.0 / 6784.0)
+                .add_scaled(&k6, h_try * 11.0 / 84.0);
+            let k7 = system.derivative(&y_new);
+
+            let error = State::zeros(y.links())
+                .add_scaled(&k1, h_try * 71.0 / 57600.0)
+                .add_scaled(&k3, h_try * -71.0 / 16695.0)
+                .add_scaled(&k4, h_try * 71.0 / 1920.0)
+                .add_scaled(&k5, h_try * -17253.0 / 339200.0)
+                .add_scaled(&k6, h_try * 22.0 / 525.0)
+                .add_scaled(&k7, -h_try / 40.0);
+            let error = self.error_norm(&error, &y, &y_new);
+
+            let finite = y_new.is_finite() && k7.is_finite();
+            let forced =
+                h_try <= min_step || self.accepted_steps + self.rejected_steps >= Self::MAX_STEPS;
+            if finite && (error <= 1.0 || forced) {
+                time = if h_try < remaining {
+                    time + h_try
+                } else {
+                    delta_time
+                };
+                y = y_new;
+                k1 = k7;
+                self.accepted_steps += 1;
+                if error > 1.0 {
+                    self.forced_steps += 1;
+                }
+            } else if !finite && h_try <= min_step {
+                // Even the smallest step leads somewhere undefined. Hand back an undefined state,
+                // so that the caller sees the step diverge rather than fall short of its time.
+                return y_new.add_scaled(&k7, h_try);
+            } else {
+                self.rejected_steps += 1;
+            }
+
+            let factor = if !finite || error.is_nan() {
+                0.2
+            } else if error == 0.0 {
+                5.0
+            } else {
+                (0.9 * error.powf(-0.2)).clamp(0.2, 5.0)
+            };
+            h = (h_try * factor).max(min_step);
+        }
+
+        self.step_size = h;
+        y
+    }
+}
+
+/// A point in phase space in canonical coordinates, angles and their conjugate momenta, in `f64`.
+///
+/// The symplectic schemes take the whole of a step in these, implicit stages included, and only
+/// round the result to `f32` once at the end. That rounding is to nearest, so the energy error it
+/// causes wanders rather than drifting steadily as a biased rounding inside the solves would.
+struct Canonical {
+    q: Vec<f64>,
+    p: Vec<f64>,
+}
+
+/// Outcome of one step of a symplectic scheme: `Err` if an implicit stage did not converge, with
+/// the state its last iterates lead to.
+type Solution = Result<Canonical, Canonical>;
+
+/// Times a step is halved to get an implicit stage to converge, before settling for its last
+/// iterate.
+const MAX_SUBDIVISIONS: u32 = 8;
+
+impl Canonical {
+    /// Advances `state` by `delta_time` with `scheme`, halving the step where an implicit stage
+    /// fails to converge. Also returns the number of stages still unconverged.
+    fn step(
+        system: &NPendulum,
+        state: &State,
+        delta_time: f32,
+        scheme: fn(&NPendulum, &[f64], &[f64], f64) -> Solution,
+    ) -> (State, u32) {
+        let q: Vec<f64> = state.angles.iter().map(|&q| q as f64).collect();
+        let v: Vec<f64> = state.velocities.iter().map(|&v| v as f64).collect();
+        let start = Canonical {
+            p: system.momenta(&q, &v),
+            q,
+        };
+
+        let mut unconverged = 0;
+        let end = start.advance(system, delta_time as f64, scheme, 0, &mut unconverged);
+        let velocities = system.velocities_from_momenta(&end.q, &end.p);
+
+        let state = State {
+            angles: end.q.iter().map(|&q| q as f32).collect(),
+            velocities: velocities.iter().map(|&v| v as f32).collect(),
+        };
+        (state, unconverged)
+    }
+
+    fn advance(
+        self,
+        system: &NPendulum,
+        delta_time: f64,
+        scheme: fn(&NPendulum, &[f64], &[f64], f64) -> Solution,
+        depth: u32,
+        unconverged: &mut u32,
+    ) -> Self {
+        match scheme(system, &self.q, &self.p, delta_time) {
+            Ok(end) => end,
+            Err(end) if depth == MAX_SUBDIVISIONS => {
+                *unconverged += 1;
+                end
+            }
+            Err(_) => {
+                let half = 0.5 * delta_time;
+                self.advance(system, half, scheme, depth + 1, unconverged)
+                    .advance(system, half, scheme, depth + 1, unconverged)
+            }
+        }
+    }
+}
+
+/// Rate of change of the momenta, `Q - ∂H/∂θ`, at the canonical point `(q, p)`. The generalised
+/// forces of friction are dissipative anyway, so they are taken from the `f32` model.
+fn force(system: &NPendulum, q: &[f64], p: &[f64]) -> Vec<f64> {
+    let v = system.velocities_from_momenta(q, p);
+    let state = State {
+        angles: q.iter().map(|&q| q as f32).collect(),
+        velocities: v.iter().map(|&v| v as f32).collect(),
+    };
+
+    system
+        .generalized_forces(&state)
+        .iter()
+        .zip(system.hamiltonian_gradient(q, &v))
+        .map(|(&friction, gradient)| friction as f64 - gradient)
+        .collect()
+}
+
+/// Most iterations spent on each implicit stage of the symplectic schemes.
+const FIXED_POINT_ITERATIONS: usize = 100;
+/// Largest change between iterates, relative to their size, at which an implicit stage counts as
+/// converged: a few units in the last place.
+const FIXED_POINT_TOLERANCE: f64 = 4.0 * f64::EPSILON;
+
+/// Iterates `f` from `initial` to its fixed point, or gives up with `Err` and the last iterate.
+fn fixed_point(initial: Vec<f64>, f: impl Fn(&[f64]) -> Vec<f64>) -> Result<Vec<f64>, Vec<f64>> {
+    let mut x = initial;
+    for _ in 0..FIXED_POINT_ITERATIONS {
+        let next = f(&x);
+        let change = next
+            .iter()
+            .zip(&x)
+            .map(|(a, b)| (a - b).abs())
+            .fold(0.0, f64::max);
+        let scale = 1.0 + next.iter().map(|a| a.abs()).fold(0.0, f64::max);
+        x = next;
+
+        if change <= FIXED_POINT_TOLERANCE * scale {
+            return Ok(x);
+        }
+    }
+    Err(x)
+}
+
+/// Selectable integration scheme, stored by value in [`NPendulum`].
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum Method {
+    ExplicitEuler(ExplicitEuler),
+    SemiImplicitEuler(SemiImplicitEuler),
+    RungeKutta4(RungeKutta4),
+    VelocityVerlet(VelocityVerlet),
+    Leapfrog(Leapfrog),
+    Yoshida4(Yoshida4),
+    DormandPrince(DormandPrince),
+}
+
+impl Default for Method {
+    fn default() -> Self {
+        Method::RungeKutta4(RungeKutta4)
+    }
+}
+
+impl Method {
+    pub const ALL: [Method; 7] = [
+        Method::ExplicitEuler(ExplicitEuler),
+        Method::SemiImplicitEuler(SemiImplicitEuler),
+        Method::RungeKutta4(RungeKutta4),
+        Method::VelocityVerlet(VelocityVerlet::DEFAULT),
+        Method::Leapfrog(Leapfrog::DEFAULT),
+        Method::Yoshida4(Yoshida4::DEFAULT),
+        Method::DormandPrince(DormandPrince::DEFAULT),
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Method::ExplicitEuler(_) => "Explicit Euler",
+            Method::SemiImplicitEuler(_) => "Semi-implicit Euler",
+            Method::RungeKutta4(_) => "Runge-Kutta 4",
+            Method::VelocityVerlet(_) => "Velocity Verlet",
+            Method::Leapfrog(_) => "Leapfrog",
+            Method::Yoshida4(_) => "Yoshida 4",
+            Method::DormandPrince(_) => "Dormand-Prince 5(4)",
+        }
+    }
+
+    /// Identifier of the scheme in scenario files and on the command line.
+    pub fn key(&self) -> &'static str {
+        match self {
+            Method::ExplicitEuler(_) => "explicit_euler",
+            Method::SemiImplicitEuler(_) => "semi_implicit_euler",
+            Method::RungeKutta4(_) => "runge_kutta4",
+            Method::VelocityVerlet(_) => "velocity_verlet",
+            Method::Leapfrog(_) => "leapfrog",
+            Method::Yoshida4(_) => "yoshida4",
+            Method::DormandPrince(_) => "dormand_prince",
+        }
+    }
+
+    /// Whether `self` and `other` are the same scheme, ignoring any settings they carry.
+    pub fn same_kind(&self, other: &Method) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+
+    /// Implicit stages a symplectic scheme left unconverged during its last step; always zero
+    /// for the explicit schemes.
+    pub fn unconverged_stages(&self) -> u32 {
+        match self {
+            Method::VelocityVerlet(verlet) => verlet.unconverged_stages,
+            Method::Leapfrog(leapfrog) => leapfrog.unconverged_stages,
+            Method::Yoshida4(yoshida) => yoshida.unconverged_stages,
+            _ => 0,
+        }
+    }
+}
+
+impl std::str::FromStr for Method {
+    type Err = String;
+
+    /// Parses a [`Method::key`], giving the scheme its default settings.
+    fn from_str(key: &str) -> Result<Self, Self::Err> {
+        Method::ALL
+            .into_iter()
+            .find(|method| method.key() == key)
+            .ok_or_else(|| {
+                let keys: Vec<_> = Method::ALL.iter().map(Method::key).collect();
+                format!(
+                    "unknown integrator {key:?}; expected one of {}",
+                    keys.join(", ")
+                )
+            })
+    }
+}
+
+impl Integrator for Method {
+    fn step(&mut self, system: &NPendulum, state: &State, delta_time: f32) -> State {
+        match self {
+            Method::ExplicitEuler(integrator) => integrator.step(system, state, delta_time),
+            Method::SemiImplicitEuler(integrator) => integrator.step(system, state, delta_time),
+            Method::RungeKutta4(integrator) => integrator.step(system, state, delta_time),
+            Method::VelocityVerlet(integrator) => integrator.step(system, state, delta_time),
+            Method::Leapfrog(integrator) => integrator.step(system, state, delta_time),
+            Method::Yoshida4(integrator) => integrator.step(system, state, delta_time),
+            Method::DormandPrince(integrator) => integrator.step(system, state, delta_time),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Largest relative deviation of the total energy from its initial value over `duration`
+    /// seconds, for a double pendulum released from rest at half a radian on each arm.
+    fn energy_error(method: Method, delta_time: f32, duration: f32) -> f64 {
+        let mut pendulum = NPendulum::new(2);
+        pendulum.integrator = method;
+        pendulum.set_state(&State {
+            angles: vec![0.5, 0.5],
+            velocities: vec![0.0, 0.0],
+        });
+
+        let initial = pendulum.total_energy() as f64;
+        let mut worst: f64 = 0.0;
+        for _ in 0..(duration / delta_time).round() as usize {
+            pendulum.update(delta_time).unwrap();
+            worst = worst.max(((pendulum.total_energy() as f64 - initial) / initial).abs());
+        }
+        worst
+    }
+
+    fn order(method: Method, delta_time: f32) -> f64 {
+        let coarse = energy_error(method, delta_time, 20.0);
+        let fine = energy_error(method, 0.5 * delta_time, 20.0);
+        (coarse / fine).log2()
+    }
+
+    #[test]
+    fn symplectic_schemes_converge_at_their_order() {
+        let verlet = order(Method::VelocityVerlet(VelocityVerlet::DEFAULT), 0.05);
+        assert!(
+            (1.8..2.2).contains(&verlet),
+            "velocity Verlet order {verlet}"
+        );
+
+        let leapfrog = order(Method::Leapfrog(Leapfrog::DEFAULT), 0.05);
+        assert!((1.8..2.2).contains(&leapfrog), "leapfrog order {leapfrog}");
+
+        let yoshida = order(Method::Yoshida4(Yoshida4::DEFAULT), 0.1);
+        assert!((3.5..4.5).contains(&yoshida), "Yoshida order {yoshida}");
+    }
+
+    #[test]
+    fn symplectic_schemes_do_not_drift() {
+        for method in [
+            Method::VelocityVerlet(VelocityVerlet::DEFAULT),
+            Method::Leapfrog(Leapfrog::DEFAULT),
+            Method::Yoshida4(Yoshida4::DEFAULT),
+        ] {
+            // At this step the truncation error dominates rounding, so drift would show.
+            let early = energy_error(method, 0.05, 100.0);
+            let late = energy_error(method, 0.05, 4000.0);
+            assert!(late < 1e-2, "{}: energy error {late}", method.name());
+            assert!(
+                late < 1.15 * early,
+                "{}: {early} after 100 s, {late} after 4000 s",
+                method.name()
+            );
+        }
+    }
+
+    #[test]
+    fn implicit_stages_converge() {
+        let mut pendulum = NPendulum::new(3);
+        pendulum.set_state(&State {
+            angles: vec![2.0, -1.0, 3.0],
+            velocities: vec![4.0, -6.0, 8.0],
+        });
+
+        let mut integrator = VelocityVerlet::DEFAULT;
+        let mut state = pendulum.state();
+        for _ in 0..1000 {
+            state = integrator.step(&pendulum, &state, 0.01);
+            assert_eq!(integrator.unconverged_stages, 0);
+        }
+    }
+
+    /// Largest difference in angle from a tightly controlled reference after `duration` seconds,
+    /// taken in steps of a tenth of a second, with the number of internal steps spent.
+    fn dormand_prince_error(tolerance: f32, duration: f32) -> (f32, u32) {
+        let pendulum = NPendulum::new(2);
+        let start = State {
+            angles: vec![1.5, -1.0],
+            velocities: vec![0.0, 0.0],
+        };
+
+        let run = |integrator: &mut DormandPrince| {
+            let mut state = start.clone();
+            let mut steps = 0;
+            for _ in 0..(duration / 0.1).round() as usize {
+                state = integrator.step(&pendulum, &state, 0.1);
+                steps += integrator.accepted_steps;
+            }
+            (state, steps)
+        };
+
+        let (reference, _) = run(&mut DormandPrince::new(1e-9, 1e-9));
+        let (state, steps) = run(&mut DormandPrince::new(tolerance, tolerance));
+        let error = state
+            .angles
+            .iter()
+            .zip(&reference.angles)
+            .map(|(a, b)| (a - b).abs())
+            .fold(0.0, f32::max);
+        (error, steps)
+    }
+
+    #[test]
+    fn dormand_prince_error_follows_tolerance() {
+        let (loose, loose_steps) = dormand_prince_error(1e-3, 2.0);
+        let (tight, tight_steps) = dormand_prince_error(1e-6, 2.0);
+
+        assert!(loose < 1e-1, "error {loose} at tolerance 1e-3");
+        assert!(tight < 1e-4, "error {tight} at tolerance 1e-6");
+        assert!(tight < loose, "error {tight} at 1e-6, {loose} at 1e-3");
+        assert!(tight_steps > loose_steps);
+    }
+
+    #[test]
+    fn dormand_prince_subdivides_large_steps() {
+        let pendulum = NPendulum::new(2);
+        let state = State {
+            angles: vec![2.0, 1.0],
+            velocities: vec![0.0, 0.0],
+        };
+
+        let mut integrator = DormandPrince::DEFAULT;
+        let end = integrator.step(&pendulum, &state, 1.0);
+        assert!(end.is_finite());
+        assert!(integrator.accepted_steps > 1);
+    }
+
+    #[test]
+    fn dormand_prince_finishes_steps_it_cannot_keep_within_tolerance() {
+        let pendulum = NPendulum::new(2);
+        let start = State {
+            angles: vec![0.3, -0.2],
+            velocities: vec![0.0, 0.0],
+        };
+
+        // No f32 step meets this tolerance, yet the step must still cover the whole second.
+        let mut integrator = DormandPrince::new(1e-12, 1e-12);
+        let end = integrator.step(&pendulum, &start, 1.0);
+        assert!(integrator.forced_steps > 0);
+
+        let mut reference = start;
+        for _ in 0..1000 {
+            reference = RungeKutta4.step(&pendulum, &reference, 0.001);
+        }
+        for (a, b) in end.angles.iter().zip(&reference.angles) {
+            assert!(
+                (a - b).abs() < 1e-4,
+                "angle {a} after the step, {b} expected"
+            );
+        }
+    }
+
+    #[test]
+    fn dormand_prince_reports_an_undefined_step() {
+        let mut pendulum = NPendulum::new(2);
+        pendulum.pendula[1].arm_length = f32::NAN;
+        let state = State {
+            angles: vec![0.3, -0.2],
+            velocities: vec![0.0, 0.0],
+        };
+
+        let mut integrator = DormandPrince::DEFAULT;
+        let end = integrator.step(&pendulum, &state, 0.1);
+        assert!(!end.is_finite());
+    }
+}
@@ -0,0 +1,18 @@
+//! Double pendulum physics: the model, numerical integrators and time series recording.
+//!
+//! Nothing in here depends on a GUI, so the simulation can be driven from analysis tools as
+//! well as from the interactive app behind the `gui` feature.
+
+pub mod fractal;
+pub mod history;
+pub mod integrator;
+pub mod lyapunov;
+pub mod parallel;
+pub mod pendulum;
+pub mod poincare;
+pub mod preset;
+pub mod scenario;
+pub mod spectrum;
+pub mod sweep;
+pub mod timeline;
+pub mod trajectory;
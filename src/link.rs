@@ -0,0 +1,17 @@
+//! Permalinks in the web build: the scenario travels in the fragment of the page URL.
+
+/// The fragment of the page URL without the leading `#`, if it has one.
+pub fn fragment() -> Option<String> {
+    let hash = web_sys::window()?.location().hash().ok()?;
+    let fragment = hash.trim_start_matches('#');
+    (!fragment.is_empty()).then(|| fragment.to_string())
+}
+
+/// Replaces the fragment of the page URL and returns the resulting link.
+pub fn set_fragment(fragment: &str) -> Result<String, String> {
+    let location = web_sys::window().ok_or("no window")?.location();
+    location
+        .set_hash(fragment)
+        .and_then(|()| location.href())
+        .map_err(|e| format!("could not update the page URL: {e:?}"))
+}
@@ -0,0 +1,160 @@
+use crate::pendulum::{wrap_angle, NPendulum, State};
+
+/// Running estimate of the largest Lyapunov exponent of a pendulum, by the two-trajectory method
+/// of Benettin et al.: a shadow copy starts a small distance away in phase space, and whenever
+/// the two have drifted apart for a while the logarithm of the growth is recorded and the shadow
+/// is pulled back to the initial distance along the current separation.
+///
+/// Distances are Euclidean over angles (radians) and angular velocities (radians per second)
+/// alike. The physics runs in `f32`, so the initial distance must stay well above its precision.
+#[derive(Debug, Clone)]
+pub struct LyapunovEstimator {
+    shadow: NPendulum,
+    separation: f32,
+    renormalization_interval: f64,
+    since_renormalization: f64,
+    time: f64,
+    log_growth: f64,
+}
+
+impl LyapunovEstimator {
+    pub const DEFAULT_SEPARATION: f32 = 1e-4;
+    pub const DEFAULT_RENORMALIZATION_INTERVAL: f64 = 0.5;
+
+    /// Starts an estimate for `reference` in its current state.
+    pub fn new(reference: &NPendulum) -> Self {
+        Self::with_settings(
+            reference,
+            Self::DEFAULT_SEPARATION,
+            Self::DEFAULT_RENORMALIZATION_INTERVAL,
+        )
+    }
+
+    pub fn with_settings(
+        reference: &NPendulum,
+        separation: f32,
+        renormalization_interval: f64,
+    ) -> Self {
+        let mut estimator = Self {
+            shadow: reference.clone(),
+            separation,
+            renormalization_interval,
+            since_renormalization: 0.0,
+            time: 0.0,
+            log_growth: 0.0,
+        };
+
+        // Start off along the diagonal of phase space, so that every coordinate is perturbed.
+        let links = reference.links();
+        let component = separation / ((2 * links) as f32).sqrt();
+        let offset = State {
+            angles: vec![component; links],
+            velocities: vec![component; links],
+        };
+        estimator
+            .shadow
+            .set_state(&reference.state().add_scaled(&offset, 1.0));
+        estimator
+    }
+
+    /// Advances the shadow by `delta_time`, following any change to the parameters of
+    /// `reference`. Call it right after advancing `reference` by the same step.
+    pub fn step(&mut self, reference: &NPendulum, delta_time: f32) {
+        self.shadow.copy_parameters(reference);
+        let diverged = self.shadow.update(delta_time).is_err();
+        self.time += delta_time as f64;
+        self.since_renormalization += delta_time as f64;
+
+        if diverged || self.since_renormalization >= self.renormalization_interval {
+            self.renormalize(reference);
+        }
+    }
+
+    /// Records the growth of the separation since the last renormalisation and rescales it back
+    /// to its initial size.
+    fn renormalize(&mut self, reference: &NPendulum) {
+        let state = reference.state();
+        let difference = separation(&state, &self.shadow.state());
+        let distance = difference.iter().map(|x| x * x).sum::<f32>().sqrt();
+        self.since_renormalization = 0.0;
+
+        if !(distance.is_finite() && distance > 0.0) {
+            // The trajectories met or the shadow blew up; start it over from the reference.
+            *self = Self::with_settings(reference, self.separation, self.renormalization_interval)
+                .resumed_from(self);
+            return;
+        }
+
+        self.log_growth += (distance as f64 / self.separation as f64).ln();
+        self.shadow
+            .set_state(&state.add_scaled(&difference, self.separation / distance));
+    }
+
+    /// Carries the accumulated estimate of `previous` over to a fresh shadow.
+    fn resumed_from(mut self, previous: &Self) -> Self {
+        self.time = previous.time;
+        self.log_growth = previous.log_growth;
+        self
+    }
+
+    /// Current estimate in inverse seconds, once any time has been simulated. Regular motion
+    /// tends to zero, chaotic motion to a positive value.
+    pub fn exponent(&self) -> Option<f32> {
+        let time = self.time - self.since_renormalization;
+        (time > 0.0).then(|| (self.log_growth / time) as f32)
+    }
+
+    /// Simulated time the estimate covers.
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+}
+
+/// Phase-space displacement from `a` to `b`, taking the shorter way round for angles.
+fn separation(a: &State, b: &State) -> State {
+    State {
+        angles: a
+            .angles
+            .iter()
+            .zip(&b.angles)
+            .map(|(a, b)| wrap_angle(b - a))
+            .collect(),
+        velocities: a
+            .velocities
+            .iter()
+            .zip(&b.velocities)
+            .map(|(a, b)| b - a)
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::preset::PRESETS;
+
+    /// Runs a preset for `duration` seconds and returns its exponent estimate.
+    fn estimate(name: &str, duration: f32) -> f32 {
+        let preset = PRESETS.iter().find(|preset| preset.name == name).unwrap();
+        let mut pendulum = preset.pendulum();
+        let mut estimator = LyapunovEstimator::new(&pendulum);
+        let time_step = 0.01;
+        for _ in 0..(duration / time_step) as usize {
+            pendulum.update(time_step).unwrap();
+            estimator.step(&pendulum, time_step);
+        }
+        estimator.exponent().unwrap()
+    }
+
+    #[test]
+    fn small_swing_is_regular() {
+        let exponent = estimate("In-phase normal mode", 60.0);
+        assert!(exponent.abs() < 0.05, "exponent {exponent} per second");
+    }
+
+    #[test]
+    fn release_from_horizontal_is_chaotic() {
+        let exponent = estimate("Chaotic release", 200.0);
+        assert!(exponent > 0.5, "exponent {exponent} per second");
+    }
+}
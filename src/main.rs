@@ -1,7 +1,13 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 mod app;
-mod pendulum;
+mod colormap;
+mod files;
+mod flip_map;
+#[cfg(target_arch = "wasm32")]
+mod link;
+mod poincare_plot;
+mod spectrum_view;
 
 #[cfg(not(target_arch = "wasm32"))]
 fn main() -> eframe::Result<()> {
@@ -49,7 +55,7 @@ fn main() {
                         ..Default::default()
                     };
                     cc.egui_ctx.set_style(style);
-                    Box::new(app::App::default())
+                    Box::new(app::App::from_page_url())
                 }),
             )
             .await
@@ -0,0 +1,144 @@
+//! Evaluating a function at many indices at once, on every CPU where there are threads to use.
+
+/// Number of threads to use when not told otherwise: one per CPU.
+pub fn default_jobs() -> usize {
+    std::thread::available_parallelism().map_or(1, Into::into)
+}
+
+/// A function evaluated at every index below a count in the background, its results collected
+/// as they finish. Dropping it abandons whatever is left.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct ParallelMap<T> {
+    count: usize,
+    cancel: std::sync::Arc<std::sync::atomic::AtomicBool>,
+    receiver: std::sync::mpsc::Receiver<(usize, T)>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<T: Send + 'static> ParallelMap<T> {
+    /// Starts evaluating `run` at every index below `count` on `jobs` threads.
+    pub fn spawn(
+        count: usize,
+        jobs: usize,
+        run: impl Fn(usize) -> T + Send + Sync + 'static,
+    ) -> Self {
+        use std::sync::{
+            atomic::{AtomicBool, AtomicUsize, Ordering},
+            Arc,
+        };
+
+        let cancel = Arc::new(AtomicBool::new(false));
+        let next = Arc::new(AtomicUsize::new(0));
+        let run = Arc::new(run);
+        let (sender, receiver) = std::sync::mpsc::channel();
+
+        for _ in 0..jobs.max(1).min(count) {
+            let (cancel, next, run, sender) =
+                (cancel.clone(), next.clone(), run.clone(), sender.clone());
+            std::thread::spawn(move || loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                if index >= count || cancel.load(Ordering::Relaxed) {
+                    break;
+                }
+                if sender.send((index, run(index))).is_err() {
+                    break;
+                }
+            });
+        }
+
+        Self {
+            count,
+            cancel,
+            receiver,
+        }
+    }
+
+    /// Results finished since the last call, with their indices, in no particular order. Where
+    /// there are no threads, they are computed on the calling thread for as long as
+    /// `keep_going` allows instead.
+    pub fn finished(&mut self, _keep_going: impl FnMut() -> bool) -> Vec<(usize, T)> {
+        self.receiver.try_iter().collect()
+    }
+
+    /// Waits for every result still to come, returning them in index order.
+    pub fn wait(self) -> Vec<T> {
+        let mut results: Vec<(usize, T)> = self.receiver.iter().collect();
+        assert_eq!(results.len(), self.count, "a parallel job panicked");
+        results.sort_unstable_by_key(|(index, _)| *index);
+        results.into_iter().map(|(_, result)| result).collect()
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<T> Drop for ParallelMap<T> {
+    fn drop(&mut self) {
+        self.cancel
+            .store(true, std::sync::atomic::Ordering::Relaxed);
+    }
+}
+
+/// Browsers give us no threads, so results are computed on the calling thread when asked for.
+#[cfg(target_arch = "wasm32")]
+pub struct ParallelMap<T> {
+    count: usize,
+    next: usize,
+    run: Box<dyn Fn(usize) -> T>,
+}
+
+#[cfg(target_arch = "wasm32")]
+impl<T: Send + 'static> ParallelMap<T> {
+    pub fn spawn(
+        count: usize,
+        _jobs: usize,
+        run: impl Fn(usize) -> T + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            count,
+            next: 0,
+            run: Box::new(run),
+        }
+    }
+
+    pub fn finished(&mut self, mut keep_going: impl FnMut() -> bool) -> Vec<(usize, T)> {
+        let mut results = Vec::new();
+        while self.next < self.count && keep_going() {
+            results.push((self.next, (self.run)(self.next)));
+            self.next += 1;
+        }
+        results
+    }
+
+    pub fn wait(mut self) -> Vec<T> {
+        self.finished(|| true)
+            .into_iter()
+            .map(|(_, result)| result)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wait_returns_every_result_in_order() {
+        let squares = ParallelMap::spawn(100, 4, |index| index * index).wait();
+        assert_eq!(
+            squares,
+            (0..100).map(|index| index * index).collect::<Vec<_>>()
+        );
+        assert!(ParallelMap::spawn(0, 4, |index| index).wait().is_empty());
+    }
+
+    #[test]
+    fn finished_results_add_up_to_all_of_them() {
+        let mut map = ParallelMap::spawn(50, 3, |index| index);
+        let mut seen = [false; 50];
+        while seen.contains(&false) {
+            for (index, result) in map.finished(|| true) {
+                assert_eq!(index, result);
+                seen[index] = true;
+            }
+        }
+    }
+}
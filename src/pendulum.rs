@@ -1,25 +1,36 @@
-use eframe::egui::{vec2, Pos2};
-use std::f32::consts::TAU;
+use crate::integrator::{Integrator, Method};
+use std::f32::consts::{PI, TAU};
 
+/// A single arm of a pendulum chain. Lengths are in metres, masses in kilograms, angles in radians
+/// from the downward vertical.
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Pendulum {
-    pub pivot: Pos2,
+    /// Point the arm hangs from, in metres with the y axis pointing down.
+    pub pivot: [f32; 2],
     pub arm_length: f32,
+    /// Angle wrapped into `[-π, π)`; see [`Pendulum::unwrapped_angle`] for the cumulative one.
     pub angle: f32,
     pub mass: f32,
     pub velocity: f32,
     pub acceleration: f32,
+    /// Viscous friction at the joint this arm hangs from, in N·m·s/rad. The friction torque is
+    /// proportional to the rotation of the arm relative to the one above it.
+    pub joint_damping: f32,
+    /// Net number of full turns, positive in the direction of increasing angle.
+    pub turns: i32,
+    /// Number of times the arm has gone over the top, in either direction.
+    pub flips: u32,
 }
 
 impl Default for Pendulum {
     fn default() -> Self {
-        Self::new([0.0, 0.0], 100.0, 20.0, 0.0, 0.0, 0.0)
+        Self::new([0.0, 0.0], 1.0, 1.0, 0.0, 0.0, 0.0)
     }
 }
 
 impl Pendulum {
     pub fn new(
-        pivot: impl Into<Pos2>,
+        pivot: [f32; 2],
         arm_length: f32,
         mass: f32,
         angle: f32,
@@ -27,73 +38,693 @@ impl Pendulum {
         acceleration: f32,
     ) -> Self {
         Self {
-            pivot: pivot.into(),
+            pivot,
             arm_length,
             angle,
             mass,
             velocity,
             acceleration,
+            joint_damping: 0.0,
+            turns: 0,
+            flips: 0,
         }
     }
 
-    pub fn update(&mut self, delta_time: f32) {
-        self.angle += self.velocity * delta_time;
-        self.angle %= TAU;
-        self.velocity += self.acceleration * delta_time;
+    /// Cumulative angle, continuous across full turns.
+    #[inline]
+    pub fn unwrapped_angle(&self) -> f32 {
+        self.angle + TAU * self.turns as f32
+    }
+
+    /// Clears the turn and flip counters, keeping the current angle.
+    pub fn reset_counters(&mut self) {
+        self.turns = 0;
+        self.flips = 0;
     }
 
     #[inline]
-    pub fn position(&self) -> Pos2 {
-        self.pivot + self.arm_length * vec2(self.angle.sin(), self.angle.cos())
+    pub fn position(&self) -> [f32; 2] {
+        [
+            self.pivot[0] + self.arm_length * self.angle.sin(),
+            self.pivot[1] + self.arm_length * self.angle.cos(),
+        ]
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub struct DoublePendulum {
-    pub pendula: (Pendulum, Pendulum),
+/// Point in the phase space of an [`NPendulum`]: one angle and one angular velocity per link.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct State {
+    pub angles: Vec<f32>,
+    pub velocities: Vec<f32>,
+}
+
+impl State {
+    /// The origin of the phase space of a chain with `links` links.
+    pub fn zeros(links: usize) -> Self {
+        Self {
+            angles: vec![0.0; links],
+            velocities: vec![0.0; links],
+        }
+    }
+
+    #[inline]
+    pub fn links(&self) -> usize {
+        self.angles.len()
+    }
+
+    /// Iterates over every coordinate, angles first.
+    pub fn iter(&self) -> impl Iterator<Item = f32> + '_ {
+        self.angles.iter().chain(self.velocities.iter()).copied()
+    }
+
+    pub fn is_finite(&self) -> bool {
+        self.iter().all(f32::is_finite)
+    }
+
+    /// Returns `self + h * other`, component-wise.
+    pub fn add_scaled(&self, other: &Self, h: f32) -> Self {
+        Self {
+            angles: add_scaled(&self.angles, &other.angles, h),
+            velocities: add_scaled(&self.velocities, &other.velocities, h),
+        }
+    }
+}
+
+/// Chain of pendula, each hanging from the bob of the previous one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NPendulum {
+    pub pendula: Vec<Pendulum>,
     pub gravity: f32,
-    pub damping: f32,
+    /// Quadratic air drag coefficient of every bob, in kg/m, so that the drag force is
+    /// `-air_drag * |v| * v`.
+    pub air_drag: f32,
+    pub integrator: Method,
 }
 
-impl Default for DoublePendulum {
+/// The classic two-link chain, which is what [`NPendulum::default`] builds.
+pub type DoublePendulum = NPendulum;
+
+impl Default for NPendulum {
     fn default() -> Self {
-        Self {
-            pendula: (Pendulum::default(), Pendulum::default()),
+        Self::new(2)
+    }
+}
+
+impl NPendulum {
+    /// A chain of `links` default arms hanging straight down at rest.
+    pub fn new(links: usize) -> Self {
+        let mut pendulum = Self {
+            pendula: vec![Pendulum::default(); links],
             gravity: 9.81,
-            damping: 0.001,
+            air_drag: 0.0,
+            integrator: Method::default(),
+        };
+        pendulum.update_pivots();
+        pendulum
+    }
+
+    #[inline]
+    pub fn links(&self) -> usize {
+        self.pendula.len()
+    }
+
+    /// Appends a copy of the last arm, hanging straight down at rest.
+    pub fn add_link(&mut self) {
+        let last = self.pendula.last().copied().unwrap_or_default();
+        self.pendula.push(Pendulum {
+            pivot: last.position(),
+            angle: 0.0,
+            velocity: 0.0,
+            acceleration: 0.0,
+            turns: 0,
+            flips: 0,
+            ..last
+        });
+        self.set_state(&self.state());
+    }
+
+    /// Removes the last arm, always keeping at least one.
+    pub fn remove_link(&mut self) {
+        if self.pendula.len() > 1 {
+            self.pendula.pop();
+            self.set_state(&self.state());
+        }
+    }
+
+    /// Advances the pendulum by `delta_time`. If the step does not lead to a finite state, the
+    /// pendulum is left as it was and [`Diverged`] returned.
+    pub fn update(&mut self, delta_time: f32) -> Result<(), Diverged> {
+        let mut integrator = self.integrator;
+        let state = integrator.step(self, &self.state(), delta_time);
+        self.integrator = integrator;
+
+        if !state.is_finite() {
+            return Err(Diverged);
+        }
+
+        let turns: Vec<i32> = self.pendula.iter().map(|p| p.turns).collect();
+        self.set_state(&state);
+
+        for (pendulum, turns) in self.pendula.iter_mut().zip(turns) {
+            pendulum.flips = pendulum
+                .flips
+                .saturating_add(pendulum.turns.abs_diff(turns));
+        }
+        Ok(())
+    }
+
+    pub fn state(&self) -> State {
+        State {
+            angles: self.pendula.iter().map(|p| p.angle).collect(),
+            velocities: self.pendula.iter().map(|p| p.velocity).collect(),
+        }
+    }
+
+    /// Moves every link to `state` and recomputes the accelerations. Angles are wrapped, with any
+    /// whole turns added to the turn counters; flips are only counted by [`NPendulum::update`].
+    pub fn set_state(&mut self, state: &State) {
+        let accelerations = self.accelerations(state);
+
+        for (i, pendulum) in self.pendula.iter_mut().enumerate() {
+            pendulum.angle = wrap_angle(state.angles[i]);
+            // Saturates, as does the cast, rather than overflow on an angle out of all range.
+            let turns = ((state.angles[i] - pendulum.angle) / TAU).round() as i32;
+            pendulum.turns = pendulum.turns.saturating_add(turns);
+            pendulum.velocity = state.velocities[i];
+            pendulum.acceleration = accelerations[i];
+        }
+
+        self.update_pivots();
+    }
+
+    /// Hangs every arm from the bob of the one before it.
+    pub fn update_pivots(&mut self) {
+        for i in 1..self.pendula.len() {
+            self.pendula[i].pivot = self.pendula[i - 1].position();
+        }
+    }
+
+    /// Time derivative of `state` under the equations of motion.
+    pub fn derivative(&self, state: &State) -> State {
+        State {
+            angles: state.velocities.clone(),
+            velocities: self.accelerations(state),
+        }
+    }
+
+    /// Angular accelerations, from solving `M(θ) α = Q(θ, ω) - C(θ, ω) - G(θ)`.
+    pub fn accelerations(&self, state: &State) -> Vec<f32> {
+        let n = self.links();
+        let mass_matrix = self.mass_matrix(&state.angles);
+
+        let mut forces = self.generalized_forces(state);
+        for (i, force) in forces.iter_mut().enumerate() {
+            let li = self.pendula[i].arm_length;
+            let ti = state.angles[i];
+
+            for j in 0..n {
+                let lj = self.pendula[j].arm_length;
+                let tj = state.angles[j];
+                let vj = state.velocities[j];
+
+                *force -= self.mass_below(i.max(j)) * li * lj * (ti - tj).sin() * vj * vj;
+            }
+            *force -= self.gravity * self.mass_below(i) * li * ti.sin();
+        }
+
+        solve_symmetric(mass_matrix, forces)
+    }
+
+    /// Non-conservative generalised forces `Q` from joint friction and air drag.
+    pub fn generalized_forces(&self, state: &State) -> Vec<f32> {
+        let n = self.links();
+        let mut forces = vec![0.0; n];
+
+        for i in 0..n {
+            let relative = match i {
+                0 => state.velocities[0],
+                _ => state.velocities[i] - state.velocities[i - 1],
+            };
+            let torque = self.pendula[i].joint_damping * relative;
+
+            forces[i] -= torque;
+            if i > 0 {
+                forces[i - 1] += torque;
+            }
+        }
+
+        if self.air_drag > 0.0 {
+            // Velocity of each bob, and the drag force acting on it.
+            let mut velocity = [0.0, 0.0];
+            let drag: Vec<[f32; 2]> = (0..n)
+                .map(|i| {
+                    let (sin, cos) = state.angles[i].sin_cos();
+                    let speed = self.pendula[i].arm_length * state.velocities[i];
+                    velocity[0] += speed * cos;
+                    velocity[1] -= speed * sin;
+
+                    let magnitude = self.air_drag * velocity[0].hypot(velocity[1]);
+                    [-magnitude * velocity[0], -magnitude * velocity[1]]
+                })
+                .collect();
+
+            // Rotating arm `j` moves every bob from `j` down, so it feels all of their drag.
+            let mut total = [0.0, 0.0];
+            for j in (0..n).rev() {
+                total[0] += drag[j][0];
+                total[1] += drag[j][1];
+
+                let (sin, cos) = state.angles[j].sin_cos();
+                forces[j] += self.pendula[j].arm_length * (total[0] * cos - total[1] * sin);
+            }
+        }
+
+        forces
+    }
+
+    pub fn kinetic_energy(&self) -> f32 {
+        let state = self.state();
+        let momenta = self.momenta(&state.angles, &state.velocities);
+
+        0.5 * momenta
+            .iter()
+            .zip(&state.velocities)
+            .map(|(p, v)| p * v)
+            .sum::<f32>()
+    }
+
+    /// Gravitational potential energy, zero when every arm hangs straight down.
+    pub fn potential_energy(&self) -> f32 {
+        self.gravity
+            * self
+                .pendula
+                .iter()
+                .enumerate()
+                .map(|(i, p)| self.mass_below(i) * p.arm_length * (1.0 - p.angle.cos()))
+                .sum::<f32>()
+    }
+
+    /// Adopts the physical parameters and integrator of `other`, keeping the current state.
+    /// Links beyond the shorter of the two chains are left alone.
+    pub fn copy_parameters(&mut self, other: &NPendulum) {
+        self.gravity = other.gravity;
+        self.air_drag = other.air_drag;
+        if !self.integrator.same_kind(&other.integrator) {
+            self.integrator = other.integrator;
+        }
+        for (pendulum, other) in self.pendula.iter_mut().zip(&other.pendula) {
+            pendulum.mass = other.mass;
+            pendulum.arm_length = other.arm_length;
+            pendulum.joint_damping = other.joint_damping;
         }
+        self.update_pivots();
     }
+
+    /// Least energy with which any arm can pass over the top: that arm upright and every other
+    /// one hanging down. Without driving, a pendulum with less energy never flips.
+    pub fn flip_energy(&self) -> f32 {
+        (0..self.links())
+            .map(|i| 2.0 * self.gravity * self.mass_below(i) * self.pendula[i].arm_length)
+            .fold(f32::INFINITY, f32::min)
+    }
+
+    pub fn total_energy(&self) -> f32 {
+        self.kinetic_energy() + self.potential_energy()
+    }
+
+    /// Angular momentum of all bobs about the fixed pivot, positive in the direction of
+    /// increasing angle. This is the sum of the canonical momenta.
+    pub fn angular_momentum(&self) -> f32 {
+        let state = self.state();
+        self.momenta(&state.angles, &state.velocities).iter().sum()
+    }
+
+    /// Configuration-dependent mass matrix `M(θ)`, so that the kinetic energy is `½ ωᵀ M ω`.
+    pub fn mass_matrix<T: Real>(&self, angles: &[T]) -> Vec<Vec<T>> {
+        let n = self.links();
+
+        (0..n)
+            .map(|i| {
+                (0..n)
+                    .map(|j| {
+                        T::from(self.mass_below(i.max(j)))
+                            * T::from(self.pendula[i].arm_length)
+                            * T::from(self.pendula[j].arm_length)
+                            * (angles[i] - angles[j]).cos()
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Canonical momenta `p = M(θ) ω` conjugate to the angles.
+    pub fn momenta<T: Real>(&self, angles: &[T], velocities: &[T]) -> Vec<T> {
+        self.mass_matrix(angles)
+            .iter()
+            .map(|row| row.iter().zip(velocities).map(|(&m, &v)| m * v).sum())
+            .collect()
+    }
+
+    /// Angular velocities `ω = M(θ)⁻¹ p` corresponding to the canonical momenta `p`.
+    pub fn velocities_from_momenta<T: Real>(&self, angles: &[T], momenta: &[T]) -> Vec<T> {
+        solve_symmetric(self.mass_matrix(angles), momenta.to_vec())
+    }
+
+    /// Angular velocities in which bob `link` moves at `velocity`, in m/s with y down, as an
+    /// impulse on that bob would leave them: of all such motions, the one with the least kinetic
+    /// energy. Any part of `velocity` the chain cannot follow, such as pulling the first bob along
+    /// its arm, is dropped.
+    pub fn velocities_for_bob_velocity(&self, link: usize, velocity: [f32; 2]) -> Vec<f32> {
+        let n = self.links();
+        let angles: Vec<f32> = self.pendula.iter().map(|p| p.angle).collect();
+        let dot = |a: &[f32], b: &[f32]| a.iter().zip(b).map(|(a, b)| a * b).sum::<f32>();
+
+        // Rows of the Jacobian `J` of the bob's position with respect to the angles.
+        let jacobian = [
+            (0..n)
+                .map(|i| match i <= link {
+                    true => self.pendula[i].arm_length * angles[i].cos(),
+                    false => 0.0,
+                })
+                .collect::<Vec<_>>(),
+            (0..n)
+                .map(|i| match i <= link {
+                    true => -self.pendula[i].arm_length * angles[i].sin(),
+                    false => 0.0,
+                })
+                .collect::<Vec<_>>(),
+        ];
+        // An impulse `λ` on the bob gives `ω = M⁻¹ Jᵀ λ`; solve `J M⁻¹ Jᵀ λ = v` for it.
+        let responses = jacobian
+            .clone()
+            .map(|row| self.velocities_from_momenta(&angles, &row));
+        let a = [0, 1].map(|i| [0, 1].map(|j| dot(&jacobian[i], &responses[j])));
+
+        let det = a[0][0] * a[1][1] - a[0][1] * a[1][0];
+        let trace = a[0][0] + a[1][1];
+        let impulse = if det.abs() > 1e-6 * trace * trace {
+            [
+                (a[1][1] * velocity[0] - a[0][1] * velocity[1]) / det,
+                (a[0][0] * velocity[1] - a[1][0] * velocity[0]) / det,
+            ]
+        } else {
+            // The bob can only move along one direction `d`, with `J M⁻¹ Jᵀ = trace d dᵀ`.
+            let column = match a[0][0] >= a[1][1] {
+                true => [a[0][0], a[1][0]],
+                false => [a[0][1], a[1][1]],
+            };
+            let norm = column[0].hypot(column[1]);
+            let direction = [column[0] / norm, column[1] / norm];
+            let scale = dot(&direction, &velocity) / trace;
+            direction.map(|d| scale * d)
+        };
+
+        (0..n)
+            .map(|i| impulse[0] * responses[0][i] + impulse[1] * responses[1][i])
+            .collect()
+    }
+
+    /// Partial derivatives `∂H/∂θ` of the Hamiltonian at constant momenta, written in terms of
+    /// the angular velocities.
+    pub fn hamiltonian_gradient<T: Real>(&self, angles: &[T], velocities: &[T]) -> Vec<T> {
+        let n = self.links();
+
+        (0..n)
+            .map(|k| {
+                let lk = T::from(self.pendula[k].arm_length);
+
+                let coupling: T = (0..n)
+                    .map(|j| {
+                        T::from(self.mass_below(k.max(j)))
+                            * lk
+                            * T::from(self.pendula[j].arm_length)
+                            * velocities[k]
+                            * velocities[j]
+                            * (angles[k] - angles[j]).sin()
+                    })
+                    .sum();
+
+                coupling
+                    + T::from(self.gravity) * T::from(self.mass_below(k)) * lk * angles[k].sin()
+            })
+            .collect()
+    }
+
+    /// Total mass of link `i` and every link hanging below it.
+    fn mass_below(&self, i: usize) -> f32 {
+        self.pendula[i..].iter().map(|p| p.mass).sum()
+    }
+}
+
+/// The simulation blew up: a step led to angles or velocities that are not finite, typically
+/// because the time step is too large for how stiff the model is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Diverged;
+
+impl std::fmt::Display for Diverged {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "the simulation diverged; try a smaller time step")
+    }
+}
+
+impl std::error::Error for Diverged {}
+
+/// Wraps `angle` into `[-π, π)`. Angles already in range are returned unchanged, so saved states
+/// read back exactly.
+#[inline]
+pub fn wrap_angle(angle: f32) -> f32 {
+    if (-PI..PI).contains(&angle) {
+        angle
+    } else {
+        (angle + PI).rem_euclid(TAU) - PI
+    }
+}
+
+/// Returns `x + h * y`, component-wise.
+pub fn add_scaled<T: Real>(x: &[T], y: &[T], h: T) -> Vec<T> {
+    x.iter().zip(y).map(|(&x, &y)| x + h * y).collect()
+}
+
+/// Floating-point types the equations of motion can be evaluated in: `f32` for the simulation
+/// itself, `f64` inside the implicit solves of the symplectic integrators.
+pub trait Real:
+    Copy
+    + From<f32>
+    + std::ops::Add<Output = Self>
+    + std::ops::Sub<Output = Self>
+    + std::ops::Mul<Output = Self>
+    + std::ops::Div<Output = Self>
+    + std::iter::Sum
+{
+    fn sin(self) -> Self;
+    fn cos(self) -> Self;
+    fn sqrt(self) -> Self;
 }
 
-impl DoublePendulum {
-    pub fn update(&mut self, delta_time: f32) {
-        self.pendula.0.update(delta_time);
-        self.pendula.1.update(delta_time);
-
-        let g = self.gravity;
-
-        let t1 = self.pendula.0.angle;
-        let m1 = self.pendula.0.mass;
-        let v1 = self.pendula.0.velocity;
-        let l1 = self.pendula.0.arm_length;
-
-        let t2 = self.pendula.1.angle;
-        let m2 = self.pendula.1.mass;
-        let v2 = self.pendula.1.velocity;
-        let l2 = self.pendula.1.arm_length;
-
-        self.pendula.0.acceleration = ((-g * (2.0 * m1 + m2) * t1.sin())
-            - m2 * g * (t1 - 2.0 * t2).sin()
-            - 2.0 * (t1 - t2).sin() * m2 * (v2 * v2 * l2 + v1 * v1 * l1 * (t1 - t2).cos()))
-            / (l1 * (2.0 * m1 + m2 - m2 * (2.0 * t1 - 2.0 * t2).cos()));
-        self.pendula.0.velocity *= 1.0 - self.damping;
-
-        self.pendula.1.acceleration = (2.0
-            * (t1 - t2).sin()
-            * (v1 * v1 * l1 * (m1 + m2)
-                + g * (m1 + m2) * t1.cos()
-                + v2 * v2 * l2 * m2 * (t1 - t2).cos()))
-            / (l2 * (2.0 * m1 + m2 - m2 * (2.0 * t1 - 2.0 * t2).cos()));
-        self.pendula.1.velocity *= 1.0 - self.damping;
+macro_rules! impl_real {
+    ($($t:ty),*) => {$(
+        impl Real for $t {
+            #[inline]
+            fn sin(self) -> Self {
+                <$t>::sin(self)
+            }
+            #[inline]
+            fn cos(self) -> Self {
+                <$t>::cos(self)
+            }
+            #[inline]
+            fn sqrt(self) -> Self {
+                <$t>::sqrt(self)
+            }
+        }
+    )*};
+}
+
+impl_real!(f32, f64);
+
+/// Solves `A x = b` for a symmetric positive definite `A` by Cholesky decomposition.
+fn solve_symmetric<T: Real>(mut a: Vec<Vec<T>>, mut b: Vec<T>) -> Vec<T> {
+    let n = b.len();
+
+    // Overwrite the lower triangle of `a` with `L`, where `A = L Lᵀ`.
+    for j in 0..n {
+        let diagonal = a[j][j] - (0..j).map(|k| a[j][k] * a[j][k]).sum::<T>();
+        a[j][j] = diagonal.sqrt();
+
+        for i in j + 1..n {
+            let sum: T = (0..j).map(|k| a[i][k] * a[j][k]).sum();
+            a[i][j] = (a[i][j] - sum) / a[j][j];
+        }
+    }
+
+    for i in 0..n {
+        let sum: T = (0..i).map(|k| a[i][k] * b[k]).sum();
+        b[i] = (b[i] - sum) / a[i][i];
+    }
+
+    for i in (0..n).rev() {
+        let sum: T = (i + 1..n).map(|k| a[k][i] * b[k]).sum();
+        b[i] = (b[i] - sum) / a[i][i];
+    }
+
+    b
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wrap_angle_maps_into_half_open_range() {
+        assert_eq!(wrap_angle(0.5), 0.5);
+        assert_eq!(wrap_angle(-PI), -PI);
+        assert_eq!(wrap_angle(PI), -PI);
+        assert!((wrap_angle(TAU + 0.5) - 0.5).abs() < 1e-6);
+        assert!((wrap_angle(-TAU - 0.5) + 0.5).abs() < 1e-6);
+        assert!((wrap_angle(7.0 * TAU + 1.0) - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn set_state_counts_turns_but_not_flips() {
+        let mut pendulum = NPendulum::new(1);
+        pendulum.set_state(&State {
+            angles: vec![2.0 * TAU + 0.5],
+            velocities: vec![0.0],
+        });
+
+        let arm = pendulum.pendula[0];
+        assert!((arm.angle - 0.5).abs() < 1e-5);
+        assert_eq!(arm.turns, 2);
+        assert_eq!(arm.flips, 0);
+        assert!((arm.unwrapped_angle() - (2.0 * TAU + 0.5)).abs() < 1e-5);
+    }
+
+    #[test]
+    fn update_counts_flips_over_the_top() {
+        for direction in [1.0, -1.0] {
+            let mut pendulum = NPendulum::new(1);
+            // Fast enough to go over the top: ½ l ω² > 2 g.
+            pendulum.set_state(&State {
+                angles: vec![0.0],
+                velocities: vec![direction * 10.0],
+            });
+
+            let mut previous = pendulum.pendula[0].unwrapped_angle();
+            for _ in 0..500 {
+                pendulum.update(0.01).unwrap();
+                let unwrapped = pendulum.pendula[0].unwrapped_angle();
+                assert!((unwrapped - previous).abs() < 0.2, "unwrapped angle jumped");
+                previous = unwrapped;
+            }
+
+            let arm = pendulum.pendula[0];
+            assert!(
+                arm.turns.signum() == direction as i32,
+                "turns {}",
+                arm.turns
+            );
+            assert_eq!(arm.flips, arm.turns.unsigned_abs());
+            assert_eq!(arm.turns, (previous / TAU).round() as i32);
+        }
+    }
+
+    #[test]
+    fn swinging_below_the_top_never_flips() {
+        let mut pendulum = NPendulum::new(1);
+        pendulum.set_state(&State {
+            angles: vec![3.0],
+            velocities: vec![0.0],
+        });
+
+        for _ in 0..1000 {
+            pendulum.update(0.01).unwrap();
+        }
+        assert_eq!(pendulum.pendula[0].flips, 0);
+        assert_eq!(pendulum.pendula[0].turns, 0);
+    }
+
+    #[test]
+    fn update_rejects_a_diverging_step() {
+        // Too coarse a step for such short arms and uneven masses.
+        let mut pendulum = NPendulum::new(3);
+        pendulum.gravity = 25.0;
+        for (link, mass) in pendulum.pendula.iter_mut().zip([10.0, 0.1, 0.1]) {
+            link.mass = mass;
+            link.arm_length = 0.1;
+        }
+        pendulum.set_state(&State {
+            angles: vec![1.0, 2.0, 3.0],
+            velocities: vec![0.0; 3],
+        });
+
+        let diverged = (0..1000).any(|_| pendulum.update(0.05).is_err());
+        assert!(diverged);
+        assert!(pendulum.state().is_finite());
+        assert!(pendulum.pendula.iter().all(|p| p.turns.abs() < 1000));
+    }
+
+    #[test]
+    fn set_state_saturates_turn_counters() {
+        let mut pendulum = NPendulum::new(2);
+        for _ in 0..2 {
+            pendulum.set_state(&State {
+                angles: vec![1e30, f32::NAN],
+                velocities: vec![0.0, f32::INFINITY],
+            });
+        }
+        assert_eq!(pendulum.pendula[0].turns, i32::MAX);
+        assert_eq!(pendulum.pendula[1].turns, 0);
+    }
+
+    /// Velocity of bob `link` when the arms turn at `velocities`, from the derivative of
+    /// [`Pendulum::position`].
+    fn bob_velocity(pendulum: &NPendulum, link: usize, velocities: &[f32]) -> [f32; 2] {
+        pendulum.pendula[..=link]
+            .iter()
+            .zip(velocities)
+            .fold([0.0, 0.0], |[x, y], (arm, omega)| {
+                [
+                    x + arm.arm_length * omega * arm.angle.cos(),
+                    y - arm.arm_length * omega * arm.angle.sin(),
+                ]
+            })
+    }
+
+    #[test]
+    fn bob_velocity_is_reproduced() {
+        let mut pendulum = NPendulum::new(3);
+        pendulum.pendula[1].arm_length = 0.7;
+        pendulum.pendula[2].mass = 2.5;
+        pendulum.set_state(&State {
+            angles: vec![0.4, -1.1, 2.0],
+            velocities: vec![0.0; 3],
+        });
+
+        for link in 1..3 {
+            let target = [0.7, -0.3];
+            let velocities = pendulum.velocities_for_bob_velocity(link, target);
+            let actual = bob_velocity(&pendulum, link, &velocities);
+            assert!(
+                (actual[0] - target[0]).abs() < 1e-5 && (actual[1] - target[1]).abs() < 1e-5,
+                "bob {link} moves at {actual:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn single_arm_takes_the_tangential_part() {
+        let mut pendulum = NPendulum::new(1);
+        pendulum.pendula[0].arm_length = 1.5;
+        let angle = 0.6_f32;
+        pendulum.set_state(&State {
+            angles: vec![angle],
+            velocities: vec![0.0],
+        });
+
+        let velocity = [0.8, 0.5];
+        let tangential = velocity[0] * angle.cos() - velocity[1] * angle.sin();
+        let velocities = pendulum.velocities_for_bob_velocity(0, velocity);
+        assert_eq!(velocities.len(), 1);
+        assert!((velocities[0] - tangential / 1.5).abs() < 1e-6);
     }
 }
@@ -0,0 +1,233 @@
+use crate::pendulum::{wrap_angle, NPendulum, State};
+use std::f32::consts::{PI, TAU};
+
+/// Which way a trajectory has to pass through a [`Section`] to be recorded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// The sectioning angle increases through the section, i.e. its velocity is positive.
+    Increasing,
+    Decreasing,
+    Both,
+}
+
+/// A Poincaré section: the hypersurface where one arm's angle takes a given value, plotted in
+/// the angle and angular velocity of another arm.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Section {
+    /// Index of the arm whose angle defines the section.
+    pub link: usize,
+    /// Angle of that arm on the section, in radians.
+    pub angle: f32,
+    pub direction: Direction,
+    /// Index of the arm whose angle and angular velocity are recorded.
+    pub plotted: usize,
+}
+
+impl Default for Section {
+    /// `θ1 = 0` crossed with positive `ω1`, recording `(θ2, ω2)`.
+    fn default() -> Self {
+        Self {
+            link: 0,
+            angle: 0.0,
+            direction: Direction::Increasing,
+            plotted: 1,
+        }
+    }
+}
+
+impl Section {
+    /// Where a step from `before` to `after` crosses the section, as the plotted arm's angle and
+    /// angular velocity, interpolated linearly to the crossing.
+    pub fn crossing(&self, before: &State, after: &State) -> Option<[f32; 2]> {
+        let d0 = wrap_angle(before.angles.get(self.link)? - self.angle);
+        let d1 = wrap_angle(after.angles.get(self.link)? - self.angle);
+
+        let increasing = d0 < 0.0 && d1 >= 0.0;
+        let decreasing = d0 >= 0.0 && d1 < 0.0;
+        // A sign change across the wrap-around at ±π is not a crossing.
+        if (d1 - d0).abs() >= PI {
+            return None;
+        }
+        let recorded = match self.direction {
+            Direction::Increasing => increasing,
+            Direction::Decreasing => decreasing,
+            Direction::Both => increasing || decreasing,
+        };
+        if !recorded {
+            return None;
+        }
+
+        let t = d0 / (d0 - d1);
+        let angle = before.angles.get(self.plotted)?;
+        let velocity = before.velocities.get(self.plotted)?;
+        Some([
+            wrap_angle(angle + t * wrap_angle(after.angles[self.plotted] - angle)),
+            velocity + t * (after.velocities[self.plotted] - velocity),
+        ])
+    }
+
+    /// A state of `model` on the section with total energy `energy`, the plotted arm at `point`
+    /// and any other arms hanging at rest. The sectioning arm gets whatever velocity makes up the
+    /// energy, in the recorded direction. `None` if `point` is out of reach at that energy.
+    pub fn start(&self, model: &NPendulum, energy: f32, point: [f32; 2]) -> Option<State> {
+        let (k, j) = (self.link, self.plotted);
+        let (mut state, potential, mass) = self.place(model, point)?;
+
+        // Kinetic energy ½ωᵀMω is a quadratic in the sectioning arm's velocity.
+        let a = 0.5 * mass[k][k];
+        let b = mass[k][j] * point[1];
+        let c = 0.5 * mass[j][j] * point[1].powi(2) - (energy - potential);
+        let discriminant = b * b - 4.0 * a * c;
+        if discriminant < 0.0 {
+            return None;
+        }
+
+        let positive = (-b + discriminant.sqrt()) / (2.0 * a);
+        let negative = (-b - discriminant.sqrt()) / (2.0 * a);
+        state.velocities[k] = match self.direction {
+            Direction::Increasing | Direction::Both if positive > 0.0 => positive,
+            Direction::Decreasing if negative < 0.0 => negative,
+            _ => return None,
+        };
+        Some(state)
+    }
+
+    /// Up to `count` starts at total energy `energy` spread over the part of the section within
+    /// reach, for filling the section with orbits.
+    pub fn starts(&self, model: &NPendulum, energy: f32, count: usize) -> Vec<State> {
+        const SCAN: usize = 360;
+
+        let angles: Vec<f32> = (0..SCAN)
+            .map(|i| wrap_angle(TAU * (i as f32 + 0.5) / SCAN as f32))
+            .filter(|&angle| self.start(model, energy, [angle, 0.0]).is_some())
+            .collect();
+        if angles.is_empty() {
+            return Vec::new();
+        }
+
+        (0..count)
+            .filter_map(|i| {
+                let angle = angles[i * angles.len() / count];
+                let (_, potential, mass) = self.place(model, [angle, 0.0])?;
+                // Below this speed of the plotted arm the sectioning arm can cross either way.
+                let max_velocity = (2.0 * (energy - potential) / mass[self.plotted][self.plotted])
+                    .max(0.0)
+                    .sqrt();
+                // Spread the velocities by the golden ratio, so that nearby angles differ.
+                let fraction = 2.0 * (i as f32 * 0.618_034).fract() - 1.0;
+                self.start(model, energy, [angle, 0.95 * fraction * max_velocity])
+            })
+            .collect()
+    }
+
+    /// The state on the section with the plotted arm at `point` and everything else at rest,
+    /// with its potential energy and mass matrix.
+    fn place(&self, model: &NPendulum, point: [f32; 2]) -> Option<(State, f32, Vec<Vec<f32>>)> {
+        let (k, j) = (self.link, self.plotted);
+        if k == j || k >= model.links() || j >= model.links() {
+            return None;
+        }
+
+        let mut state = State::zeros(model.links());
+        state.angles[k] = self.angle;
+        [state.angles[j], state.velocities[j]] = point;
+
+        let mut pendulum = model.clone();
+        pendulum.set_state(&state);
+        let mass = pendulum.mass_matrix(&state.angles);
+        Some((state, pendulum.potential_energy(), mass))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(angles: [f32; 2], velocities: [f32; 2]) -> State {
+        State {
+            angles: angles.to_vec(),
+            velocities: velocities.to_vec(),
+        }
+    }
+
+    #[test]
+    fn crossing_is_interpolated_onto_the_section() {
+        let section = Section {
+            angle: 0.5,
+            ..Section::default()
+        };
+        // The sectioning arm goes from 0.4 to 0.8, reaching 0.5 a quarter of the way through.
+        let before = state([0.4, 1.0], [2.0, -1.0]);
+        let after = state([0.8, 2.0], [2.0, 3.0]);
+
+        let [angle, velocity] = section.crossing(&before, &after).unwrap();
+        assert!((angle - 1.25).abs() < 1e-5, "angle {angle}");
+        assert!(velocity.abs() < 1e-5, "velocity {velocity}");
+    }
+
+    #[test]
+    fn crossing_respects_the_direction() {
+        let up = [
+            state([-0.1, 0.0], [1.0, 0.0]),
+            state([0.1, 0.0], [1.0, 0.0]),
+        ];
+        let down = [up[1].clone(), up[0].clone()];
+        let section = |direction| Section {
+            direction,
+            ..Section::default()
+        };
+
+        let increasing = section(Direction::Increasing);
+        assert!(increasing.crossing(&up[0], &up[1]).is_some());
+        assert!(increasing.crossing(&down[0], &down[1]).is_none());
+
+        let decreasing = section(Direction::Decreasing);
+        assert!(decreasing.crossing(&up[0], &up[1]).is_none());
+        assert!(decreasing.crossing(&down[0], &down[1]).is_some());
+
+        let both = section(Direction::Both);
+        assert!(both.crossing(&up[0], &up[1]).is_some());
+        assert!(both.crossing(&down[0], &down[1]).is_some());
+
+        // Going over the top from just below π to just above -π is not a crossing of θ = 0.
+        let over = [
+            state([3.1, 0.0], [1.0, 0.0]),
+            state([-3.1, 0.0], [1.0, 0.0]),
+        ];
+        assert!(both.crossing(&over[0], &over[1]).is_none());
+    }
+
+    #[test]
+    fn start_has_the_requested_energy() {
+        let model = NPendulum::new(2);
+        let energy = 0.5 * model.flip_energy();
+
+        for direction in [Direction::Increasing, Direction::Decreasing] {
+            let section = Section {
+                direction,
+                ..Section::default()
+            };
+            let start = section.start(&model, energy, [0.3, 0.5]).unwrap();
+            assert_eq!(start.angles[0], section.angle);
+            assert_eq!([start.angles[1], start.velocities[1]], [0.3, 0.5]);
+            match direction {
+                Direction::Increasing => assert!(start.velocities[0] > 0.0),
+                _ => assert!(start.velocities[0] < 0.0),
+            }
+
+            let mut pendulum = model.clone();
+            pendulum.set_state(&start);
+            let error = (pendulum.total_energy() - energy).abs();
+            assert!(error < 1e-4 * energy.abs(), "energy off by {error} J");
+        }
+    }
+
+    #[test]
+    fn start_is_out_of_reach_below_its_potential_energy() {
+        let model = NPendulum::new(2);
+        let energy = 0.01 * model.flip_energy();
+        assert!(Section::default()
+            .start(&model, energy, [1.0, 0.0])
+            .is_none());
+    }
+}
@@ -0,0 +1,249 @@
+//! The Poincaré section plot: where the pendulum pierces a section of phase space, accumulated
+//! over runs at different energies and, optionally, over a batch of orbits at a single energy.
+
+use std::f64::consts::PI;
+
+use double_pendulum::{
+    pendulum::{NPendulum, State},
+    poincare::{Direction, Section},
+};
+use eframe::egui;
+
+/// The piercings of the section by a single trajectory.
+struct Orbit {
+    /// Total energy of the trajectory when it first crossed the section.
+    energy: f32,
+    points: Vec<[f64; 2]>,
+}
+
+/// A pendulum of the batch, simulated off screen.
+struct BatchMember {
+    pendulum: NPendulum,
+    /// Index of its orbit in [`PoincarePlot::orbits`].
+    orbit: usize,
+}
+
+pub struct PoincarePlot {
+    section: Section,
+    orbits: Vec<Orbit>,
+    /// Orbit the crossings of the main pendulum go to, created at its first crossing.
+    current: Option<usize>,
+    batch: Vec<BatchMember>,
+    /// Number of orbits a batch launches.
+    batch_size: usize,
+    /// Simulated seconds per second of wall clock for the batch.
+    batch_speed: f32,
+}
+
+impl Default for PoincarePlot {
+    fn default() -> Self {
+        Self {
+            section: Section::default(),
+            orbits: Vec::new(),
+            current: None,
+            batch: Vec::new(),
+            batch_size: 24,
+            batch_speed: 20.0,
+        }
+    }
+}
+
+impl PoincarePlot {
+    /// Records any crossing of the section by the step of `pendulum` from `before`.
+    pub fn record(&mut self, before: &State, pendulum: &NPendulum) {
+        let Some([angle, velocity]) = self.section.crossing(before, &pendulum.state()) else {
+            return;
+        };
+
+        let orbits = &mut self.orbits;
+        let current = *self.current.get_or_insert_with(|| {
+            orbits.push(Orbit {
+                energy: pendulum.total_energy(),
+                points: Vec::new(),
+            });
+            orbits.len() - 1
+        });
+        orbits[current].points.push([angle as f64, velocity as f64]);
+    }
+
+    /// Sends later crossings of the main pendulum to a new orbit, keeping the old ones.
+    pub fn new_run(&mut self) {
+        self.current = None;
+    }
+
+    /// Advances the batch by `frame_time` seconds of wall clock.
+    pub fn advance_batch(&mut self, frame_time: f32, time_step: f32) {
+        if self.batch.is_empty() {
+            return;
+        }
+
+        // Cap the frame time so that a stalled frame does not stall the next one too.
+        let steps = (self.batch_speed * frame_time.min(0.1) / time_step).ceil() as usize;
+        let (section, orbits) = (&self.section, &mut self.orbits);
+        // Members that blow up are dropped, keeping the crossings they made until then.
+        self.batch.retain_mut(|member| {
+            for _ in 0..steps {
+                let before = member.pendulum.state();
+                if member.pendulum.update(time_step).is_err() {
+                    return false;
+                }
+                if let Some([angle, velocity]) = section.crossing(&before, &member.pendulum.state())
+                {
+                    orbits[member.orbit]
+                        .points
+                        .push([angle as f64, velocity as f64]);
+                }
+            }
+            true
+        });
+    }
+
+    /// Shows the section and its controls.
+    pub fn ui(&mut self, ui: &mut egui::Ui, model: &NPendulum) {
+        let links = model.links();
+        if links < 2 {
+            ui.centered_and_justified(|ui| {
+                ui.label("The Poincaré section needs at least two links.")
+            });
+            return;
+        }
+        if self.section.link >= links || self.section.plotted >= links {
+            self.set_section(Section::default());
+        }
+        if !self.batch.is_empty() {
+            ui.ctx().request_repaint();
+        }
+
+        let mut section = self.section;
+        ui.horizontal_wrapped(|ui| {
+            ui.label("Section: θ");
+            link_combo(ui, "poincare_link", &mut section.link, links);
+            ui.label("=");
+            ui.drag_angle(&mut section.angle);
+            egui::ComboBox::from_id_source("poincare_direction")
+                .selected_text(direction_name(section.direction))
+                .show_ui(ui, |ui| {
+                    for direction in [
+                        Direction::Increasing,
+                        Direction::Decreasing,
+                        Direction::Both,
+                    ] {
+                        ui.selectable_value(
+                            &mut section.direction,
+                            direction,
+                            direction_name(direction),
+                        );
+                    }
+                });
+            ui.separator();
+            ui.label("Plot arm");
+            link_combo(ui, "poincare_plotted", &mut section.plotted, links);
+            ui.separator();
+            if ui.button("Clear").clicked() {
+                self.clear();
+            }
+        });
+        if section != self.section {
+            self.set_section(section);
+        }
+
+        ui.horizontal_wrapped(|ui| {
+            let energy = model.total_energy();
+            ui.label("Batch of");
+            ui.add(egui::DragValue::new(&mut self.batch_size).clamp_range(1..=200));
+            ui.label("orbits");
+            if ui
+                .button(format!("Launch at E = {energy:.2} J"))
+                .on_hover_text("Fill the section with orbits at the current total energy")
+                .clicked()
+            {
+                self.launch_batch(model, energy);
+            }
+            if !self.batch.is_empty() && ui.button("Stop").clicked() {
+                self.batch.clear();
+            }
+            ui.label("Speed:");
+            ui.add(
+                egui::Slider::new(&mut self.batch_speed, 1.0..=200.0)
+                    .logarithmic(true)
+                    .suffix("×"),
+            );
+        });
+
+        if self.section.link == self.section.plotted {
+            ui.centered_and_justified(|ui| {
+                ui.label("Plot an arm other than the one that defines the section.")
+            });
+            return;
+        }
+
+        let plotted = self.section.plotted + 1;
+        egui_plot::Plot::new("poincare")
+            .allow_zoom(true)
+            .allow_scroll(false)
+            .allow_drag(true)
+            .x_axis_label(format!("θ{plotted}"))
+            .y_axis_label(format!("ω{plotted} (rad/s)"))
+            .x_axis_formatter(|value, _, _| format!("{:.2}π", value / PI))
+            .legend(egui_plot::Legend::default())
+            .show(ui, |plot_ui| {
+                for (i, orbit) in self.orbits.iter().enumerate() {
+                    // Successive orbits a golden angle apart in hue stay distinguishable.
+                    let hue = (i as f32 * 0.618_034).fract();
+                    plot_ui.points(
+                        egui_plot::Points::new(orbit.points.clone())
+                            .radius(1.5)
+                            .color(egui::ecolor::Hsva::new(hue, 0.8, 0.9, 1.0))
+                            .name(format!("E = {:.2} J", orbit.energy)),
+                    );
+                }
+            });
+    }
+
+    /// Starts `batch_size` orbits of `model` at total energy `energy` spread over the section.
+    fn launch_batch(&mut self, model: &NPendulum, energy: f32) {
+        for state in self.section.starts(model, energy, self.batch_size) {
+            let mut pendulum = model.clone();
+            pendulum.set_state(&state);
+            self.orbits.push(Orbit {
+                energy,
+                points: Vec::new(),
+            });
+            self.batch.push(BatchMember {
+                pendulum,
+                orbit: self.orbits.len() - 1,
+            });
+        }
+    }
+
+    /// Switches to `section`; points recorded on another section are dropped.
+    fn set_section(&mut self, section: Section) {
+        self.section = section;
+        self.clear();
+    }
+
+    fn clear(&mut self) {
+        self.orbits.clear();
+        self.current = None;
+        self.batch.clear();
+    }
+}
+
+fn link_combo(ui: &mut egui::Ui, id: &str, link: &mut usize, links: usize) {
+    egui::ComboBox::from_id_source(id)
+        .width(40.0)
+        .selected_text((*link + 1).to_string())
+        .show_ui(ui, |ui| {
+            for i in 0..links {
+                ui.selectable_value(link, i, (i + 1).to_string());
+            }
+        });
+}
+
+fn direction_name(direction: Direction) -> &'static str {
+    match direction {
+        Direction::Increasing => "crossed forwards",
+        Direction::Decreasing => "crossed backwards",
+        Direction::Both => "crossed either way",
+    }
+}
@@ -0,0 +1,85 @@
+use crate::{
+    pendulum::{NPendulum, State},
+    scenario::{Scenario, SimulationSettings},
+};
+use std::f32::consts::{FRAC_PI_2, PI, SQRT_2};
+
+/// A named initial condition of the default double pendulum: two 1 kg bobs on 1 m arms, no
+/// damping, standard gravity.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Preset {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// Initial angles in radians from the downward vertical.
+    pub angles: [f32; 2],
+    /// Initial angular velocities in radians per second.
+    pub velocities: [f32; 2],
+}
+
+/// Small amplitude used by the normal mode presets, in radians.
+const SMALL_ANGLE: f32 = 0.1;
+
+pub const PRESETS: &[Preset] = &[
+    Preset {
+        name: "Hanging at rest",
+        description: "Stable equilibrium: both arms straight down.",
+        angles: [0.0, 0.0],
+        velocities: [0.0, 0.0],
+    },
+    Preset {
+        name: "In-phase normal mode",
+        description: "Small swing with the arms in step and the lower one \u{221A}2 times further \
+                      out. Period 2.62 s.",
+        angles: [SMALL_ANGLE, SQRT_2 * SMALL_ANGLE],
+        velocities: [0.0, 0.0],
+    },
+    Preset {
+        name: "Anti-phase normal mode",
+        description: "Small swing with the arms in opposition and the lower one \u{221A}2 times \
+                      further out. Period 1.09 s.",
+        angles: [SMALL_ANGLE, -SQRT_2 * SMALL_ANGLE],
+        velocities: [0.0, 0.0],
+    },
+    Preset {
+        name: "Inverted equilibrium",
+        description: "Unstable equilibrium: both arms straight up. Rounding errors alone are \
+                      enough to topple it.",
+        angles: [PI, PI],
+        velocities: [0.0, 0.0],
+    },
+    Preset {
+        name: "Chaotic release",
+        description: "Both arms released from horizontal. Nearby starts diverge within seconds.",
+        angles: [FRAC_PI_2, FRAC_PI_2],
+        velocities: [0.0, 0.0],
+    },
+    Preset {
+        name: "Large in-phase orbit",
+        description: "Stable periodic orbit continuing the in-phase mode to large amplitude. \
+                      Period 2.78 s.",
+        angles: [1.0, 1.062518],
+        velocities: [0.0, 0.0],
+    },
+    Preset {
+        name: "Large anti-phase orbit",
+        description: "Stable periodic orbit with the arms swinging in opposition. Period 1.97 s.",
+        angles: [1.0, -1.085119],
+        velocities: [0.0, 0.0],
+    },
+];
+
+impl Preset {
+    pub fn pendulum(&self) -> NPendulum {
+        let mut pendulum = NPendulum::new(2);
+        pendulum.set_state(&State {
+            angles: self.angles.to_vec(),
+            velocities: self.velocities.to_vec(),
+        });
+        pendulum
+    }
+
+    /// The preset with default simulation settings.
+    pub fn scenario(&self) -> Scenario {
+        Scenario::new(&self.pendulum(), SimulationSettings::default())
+    }
+}
@@ -0,0 +1,474 @@
+use crate::{
+    integrator::Method,
+    pendulum::{NPendulum, Pendulum, State},
+};
+use serde::{Deserialize, Serialize};
+
+/// Key of the scenario in a permalink's URL fragment.
+const LINK_KEY: &str = "scenario";
+
+/// Schema version written to every scenario file. Bump it whenever a change would make older
+/// readers misinterpret a file, and teach [`Scenario::parse`] to upgrade the old layout.
+pub const SCENARIO_VERSION: u32 = 1;
+
+/// A complete, shareable experiment setup: the model parameters, the initial state and the
+/// settings used to step it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Scenario {
+    pub version: u32,
+    #[serde(serialize_with = "serialize_decimal")]
+    pub gravity: f32,
+    #[serde(default, serialize_with = "serialize_decimal")]
+    pub air_drag: f32,
+    #[serde(default)]
+    pub integrator: Method,
+    #[serde(default)]
+    pub simulation: SimulationSettings,
+    pub links: Vec<Link>,
+}
+
+/// Parameters and initial conditions of a single arm.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Link {
+    #[serde(serialize_with = "serialize_decimal")]
+    pub mass: f32,
+    #[serde(serialize_with = "serialize_decimal")]
+    pub arm_length: f32,
+    #[serde(default, serialize_with = "serialize_decimal")]
+    pub joint_damping: f32,
+    /// Initial angle in radians from the downward vertical.
+    #[serde(default, serialize_with = "serialize_decimal")]
+    pub angle: f32,
+    /// Initial angular velocity in radians per second.
+    #[serde(default, serialize_with = "serialize_decimal")]
+    pub velocity: f32,
+}
+
+/// How the simulation is stepped, independent of the model itself.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SimulationSettings {
+    #[serde(serialize_with = "serialize_decimal")]
+    pub time_step: f32,
+    /// Simulated seconds per second of wall clock.
+    #[serde(serialize_with = "serialize_decimal")]
+    pub speed: f32,
+    pub max_substeps: u32,
+}
+
+impl Default for SimulationSettings {
+    fn default() -> Self {
+        Self {
+            time_step: 0.005,
+            speed: 1.0,
+            max_substeps: 64,
+        }
+    }
+}
+
+/// On-disk representation of a scenario.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Toml,
+}
+
+impl Format {
+    /// Guesses the format from a file name, by its extension.
+    pub fn from_file_name(name: &str) -> Option<Self> {
+        let (_, extension) = name.rsplit_once('.')?;
+        match extension.to_ascii_lowercase().as_str() {
+            "json" => Some(Format::Json),
+            "toml" => Some(Format::Toml),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ScenarioError {
+    Json(serde_json::Error),
+    TomlDe(toml::de::Error),
+    TomlSer(toml::ser::Error),
+    UnsupportedVersion(u32),
+    NoLinks,
+    /// A parameter is out of range, e.g. a mass that is not positive or a value that is not
+    /// finite; the message says which.
+    Invalid(String),
+    UnknownFormat(String),
+    /// The URL fragment has no scenario, or it is not validly percent-encoded.
+    InvalidLink,
+}
+
+impl std::fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScenarioError::Json(e) => write!(f, "invalid JSON scenario: {e}"),
+            ScenarioError::TomlDe(e) => write!(f, "invalid TOML scenario: {e}"),
+            ScenarioError::TomlSer(e) => write!(f, "could not write TOML scenario: {e}"),
+            ScenarioError::UnsupportedVersion(version) => write!(
+                f,
+                "scenario version {version} is newer than the supported version {SCENARIO_VERSION}"
+            ),
+            ScenarioError::NoLinks => write!(f, "scenario has no links"),
+            ScenarioError::Invalid(reason) => write!(f, "invalid scenario: {reason}"),
+            ScenarioError::UnknownFormat(name) => {
+                write!(f, "cannot tell the format of {name:?}; use .json or .toml")
+            }
+            ScenarioError::InvalidLink => write!(f, "link does not contain a valid scenario"),
+        }
+    }
+}
+
+impl std::error::Error for ScenarioError {}
+
+impl From<serde_json::Error> for ScenarioError {
+    fn from(e: serde_json::Error) -> Self {
+        ScenarioError::Json(e)
+    }
+}
+
+impl From<toml::de::Error> for ScenarioError {
+    fn from(e: toml::de::Error) -> Self {
+        ScenarioError::TomlDe(e)
+    }
+}
+
+impl From<toml::ser::Error> for ScenarioError {
+    fn from(e: toml::ser::Error) -> Self {
+        ScenarioError::TomlSer(e)
+    }
+}
+
+impl Scenario {
+    /// Captures the parameters and current state of `pendulum`.
+    pub fn new(pendulum: &NPendulum, simulation: SimulationSettings) -> Self {
+        Self {
+            version: SCENARIO_VERSION,
+            gravity: pendulum.gravity,
+            air_drag: pendulum.air_drag,
+            integrator: pendulum.integrator,
+            simulation,
+            links: pendulum
+                .pendula
+                .iter()
+                .map(|p| Link {
+                    mass: p.mass,
+                    arm_length: p.arm_length,
+                    joint_damping: p.joint_damping,
+                    angle: p.angle,
+                    velocity: p.velocity,
+                })
+                .collect(),
+        }
+    }
+
+    /// Builds the pendulum described by the scenario, in its initial state.
+    pub fn pendulum(&self) -> NPendulum {
+        let mut pendulum = NPendulum {
+            pendula: self
+                .links
+                .iter()
+                .map(|link| Pendulum {
+                    joint_damping: link.joint_damping,
+                    ..Pendulum::new([0.0, 0.0], link.arm_length, link.mass, 0.0, 0.0, 0.0)
+                })
+                .collect(),
+            gravity: self.gravity,
+            air_drag: self.air_drag,
+            integrator: self.integrator,
+        };
+
+        pendulum.set_state(&State {
+            angles: self.links.iter().map(|link| link.angle).collect(),
+            velocities: self.links.iter().map(|link| link.velocity).collect(),
+        });
+        pendulum
+    }
+
+    pub fn parse(text: &str, format: Format) -> Result<Self, ScenarioError> {
+        let scenario: Scenario = match format {
+            Format::Json => serde_json::from_str(text)?,
+            Format::Toml => toml::from_str(text)?,
+        };
+
+        if scenario.version > SCENARIO_VERSION {
+            return Err(ScenarioError::UnsupportedVersion(scenario.version));
+        }
+        scenario.validate()?;
+
+        Ok(scenario)
+    }
+
+    /// Checks that the scenario describes a pendulum that can be simulated: at least one link,
+    /// positive masses, arm lengths, time step and speed, friction that takes energy away rather
+    /// than adding it, and finite values throughout.
+    pub fn validate(&self) -> Result<(), ScenarioError> {
+        fn invalid(reason: String) -> Result<(), ScenarioError> {
+            Err(ScenarioError::Invalid(reason))
+        }
+
+        if self.links.is_empty() {
+            return Err(ScenarioError::NoLinks);
+        }
+        if !self.gravity.is_finite() {
+            return invalid(format!("gravity is {}", self.gravity));
+        }
+        if !(self.air_drag.is_finite() && self.air_drag >= 0.0) {
+            return invalid(format!("air drag {} must not be negative", self.air_drag));
+        }
+
+        for (i, link) in self.links.iter().enumerate() {
+            let i = i + 1;
+            for (name, value) in [("mass", link.mass), ("arm length", link.arm_length)] {
+                if !(value.is_finite() && value > 0.0) {
+                    return invalid(format!("link {i} has {name} {value}; it must be positive"));
+                }
+            }
+            if !(link.joint_damping.is_finite() && link.joint_damping >= 0.0) {
+                return invalid(format!(
+                    "link {i} has joint damping {}; it must not be negative",
+                    link.joint_damping
+                ));
+            }
+            for (name, value) in [("angle", link.angle), ("velocity", link.velocity)] {
+                if !value.is_finite() {
+                    return invalid(format!("link {i} has {name} {value}"));
+                }
+            }
+        }
+
+        let simulation = &self.simulation;
+        if !(simulation.time_step.is_finite() && simulation.time_step > 0.0) {
+            return invalid(format!(
+                "time step {} must be positive",
+                simulation.time_step
+            ));
+        }
+        if !(simulation.speed.is_finite() && simulation.speed > 0.0) {
+            return invalid(format!("speed {} must be positive", simulation.speed));
+        }
+        if simulation.max_substeps == 0 {
+            return invalid("at least one substep per frame is needed".to_string());
+        }
+
+        if let Method::DormandPrince(dormand_prince) = self.integrator {
+            for (name, value) in [
+                ("absolute tolerance", dormand_prince.absolute_tolerance),
+                ("relative tolerance", dormand_prince.relative_tolerance),
+            ] {
+                if !(value.is_finite() && value > 0.0) {
+                    return invalid(format!("{name} {value} must be positive"));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Parses a scenario file, telling the format from its name.
+    pub fn parse_file(name: &str, text: &str) -> Result<Self, ScenarioError> {
+        let format = Format::from_file_name(name)
+            .ok_or_else(|| ScenarioError::UnknownFormat(name.to_string()))?;
+        Self::parse(text, format)
+    }
+
+    pub fn to_string(&self, format: Format) -> Result<String, ScenarioError> {
+        Ok(match format {
+            Format::Json => serde_json::to_string_pretty(self)?,
+            Format::Toml => toml::to_string_pretty(self)?,
+        })
+    }
+
+    /// Encodes the scenario as a URL fragment (without the leading `#`), so a link can carry it.
+    pub fn to_link_fragment(&self) -> Result<String, ScenarioError> {
+        let json = serde_json::to_string(self)?;
+        Ok(format!("{LINK_KEY}={}", percent_encode(&json)))
+    }
+
+    /// Reads a scenario back from a URL fragment made by [`Scenario::to_link_fragment`]. A
+    /// leading `#` and other `&`-separated parameters are ignored.
+    pub fn parse_link_fragment(fragment: &str) -> Result<Self, ScenarioError> {
+        let encoded = fragment
+            .trim_start_matches('#')
+            .split('&')
+            .find_map(|parameter| parameter.strip_prefix(LINK_KEY)?.strip_prefix('='))
+            .ok_or(ScenarioError::InvalidLink)?;
+        let json = percent_decode(encoded).ok_or(ScenarioError::InvalidLink)?;
+        Self::parse(&json, Format::Json)
+    }
+}
+
+/// Writes an `f32` parameter as the `f64` of the same shortest decimal, so that formats that only
+/// know `f64`, like TOML, show `9.81` rather than `9.8100004196167`.
+pub(crate) fn serialize_decimal<S: serde::Serializer>(
+    value: &f32,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(value.to_string().parse().unwrap_or(f64::from(*value)))
+}
+
+/// Escapes every byte that may not appear in a URL fragment, as well as the `&` and `=` that
+/// separate its parameters.
+fn percent_encode(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b':' | b',' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    encoded
+}
+
+fn percent_decode(text: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut rest = text.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integrator::DormandPrince;
+
+    fn scenario() -> Scenario {
+        let mut pendulum = NPendulum::new(3);
+        pendulum.air_drag = 0.05;
+        pendulum.integrator = Method::DormandPrince(DormandPrince::new(1e-6, 1e-4));
+        pendulum.pendula[1].joint_damping = 0.2;
+        pendulum.set_state(&State {
+            angles: vec![1.0, -2.5, 0.25],
+            velocities: vec![0.5, 0.0, -3.0],
+        });
+        Scenario::new(&pendulum, SimulationSettings::default())
+    }
+
+    #[test]
+    fn round_trips_through_both_formats() {
+        let scenario = scenario();
+        for format in [Format::Json, Format::Toml] {
+            let text = scenario.to_string(format).unwrap();
+            assert_eq!(Scenario::parse(&text, format).unwrap(), scenario);
+        }
+    }
+
+    #[test]
+    fn rebuilds_the_pendulum() {
+        let scenario = scenario();
+        let pendulum = scenario.pendulum();
+        assert_eq!(Scenario::new(&pendulum, scenario.simulation), scenario);
+    }
+
+    #[test]
+    fn writes_parameters_as_entered() {
+        let text = scenario().to_string(Format::Toml).unwrap();
+        for line in [
+            "gravity = 9.81",
+            "time_step = 0.005",
+            "absolute_tolerance = 0.000001",
+        ] {
+            assert!(text.contains(line), "no {line:?} in\n{text}");
+        }
+    }
+
+    #[test]
+    fn rejects_newer_versions() {
+        let mut scenario = scenario();
+        scenario.version = SCENARIO_VERSION + 1;
+        let text = scenario.to_string(Format::Json).unwrap();
+        assert!(matches!(
+            Scenario::parse(&text, Format::Json),
+            Err(ScenarioError::UnsupportedVersion(version)) if version == SCENARIO_VERSION + 1
+        ));
+    }
+
+    #[test]
+    fn tells_the_format_from_the_file_name() {
+        assert_eq!(Format::from_file_name("a.JSON"), Some(Format::Json));
+        assert_eq!(Format::from_file_name("dir.v2/a.toml"), Some(Format::Toml));
+        assert_eq!(Format::from_file_name("a.yaml"), None);
+        assert!(matches!(
+            Scenario::parse_file("scenario", "{}"),
+            Err(ScenarioError::UnknownFormat(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_unsimulatable_parameters() {
+        let invalid: [fn(&mut Scenario); 13] = [
+            |s| s.links.clear(),
+            |s| s.links[0].mass = 0.0,
+            |s| s.links[1].arm_length = -1.0,
+            |s| s.links[2].angle = f32::NAN,
+            |s| s.gravity = f32::INFINITY,
+            |s| s.air_drag = -0.1,
+            |s| s.links[1].joint_damping = -0.1,
+            |s| s.simulation.speed = 0.0,
+            |s| s.simulation.speed = -1.0,
+            |s| s.simulation.time_step = 0.0,
+            |s| s.simulation.time_step = f32::INFINITY,
+            |s| s.simulation.max_substeps = 0,
+            |s| s.integrator = Method::DormandPrince(DormandPrince::new(0.0, 0.0)),
+        ];
+
+        assert!(scenario().validate().is_ok());
+        for change in invalid {
+            let mut scenario = scenario();
+            change(&mut scenario);
+            assert!(scenario.validate().is_err(), "accepted {scenario:?}");
+        }
+    }
+
+    #[test]
+    fn round_trips_through_a_link() {
+        let scenario = scenario();
+        let fragment = scenario.to_link_fragment().unwrap();
+        assert_eq!(Scenario::parse_link_fragment(&fragment).unwrap(), scenario);
+
+        let url = format!("#view=spectrum&{fragment}&zoom=2");
+        assert_eq!(Scenario::parse_link_fragment(&url).unwrap(), scenario);
+    }
+
+    #[test]
+    fn link_fragments_escape_reserved_characters() {
+        let fragment = scenario().to_link_fragment().unwrap();
+        let encoded = fragment.strip_prefix("scenario=").unwrap();
+        assert!(!encoded.contains(['&', '=', '#', '"', '{', ' ']));
+
+        let text = "a&b=c#d%e f/g?h+i\"j\u{e9}\u{1f600}";
+        assert_eq!(percent_decode(&percent_encode(text)).as_deref(), Some(text));
+    }
+
+    #[test]
+    fn rejects_broken_links() {
+        for fragment in [
+            "",
+            "#zoom=2",
+            "#scenario=%7",
+            "#scenario=%zz",
+            "#scenario=%FF",
+        ] {
+            assert!(
+                matches!(
+                    Scenario::parse_link_fragment(fragment),
+                    Err(ScenarioError::InvalidLink)
+                ),
+                "accepted {fragment:?}"
+            );
+        }
+        assert!(Scenario::parse_link_fragment("#scenario=%7B%7D").is_err());
+    }
+}
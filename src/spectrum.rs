@@ -0,0 +1,221 @@
+use crate::pendulum::NPendulum;
+use std::f64::consts::TAU;
+
+/// Taper applied to a stretch of samples before transforming it, trading frequency resolution
+/// for less leakage from the ends of the stretch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Window {
+    Rectangular,
+    Hann,
+    Hamming,
+}
+
+impl Window {
+    pub const ALL: [Window; 3] = [Window::Rectangular, Window::Hann, Window::Hamming];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Window::Rectangular => "Rectangular",
+            Window::Hann => "Hann",
+            Window::Hamming => "Hamming",
+        }
+    }
+
+    /// Weight of sample `i` of `len`.
+    pub fn weight(&self, i: usize, len: usize) -> f32 {
+        if len < 2 {
+            return 1.0;
+        }
+        let cos = (TAU * i as f64 / (len - 1) as f64).cos() as f32;
+        match self {
+            Window::Rectangular => 1.0,
+            Window::Hann => 0.5 - 0.5 * cos,
+            Window::Hamming => 0.54 - 0.46 * cos,
+        }
+    }
+}
+
+/// One-sided power spectral density of evenly spaced samples.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spectrum {
+    /// Spacing of the frequency bins, in hertz.
+    pub resolution: f32,
+    /// Power density of each bin from zero up to the Nyquist frequency, in squared units of the
+    /// samples per hertz.
+    pub power: Vec<f32>,
+}
+
+impl Spectrum {
+    /// Spectrum of `samples` taken every `interval` seconds. The mean is removed before the
+    /// window is applied, and the samples are padded with zeros to a power of two.
+    pub fn new(samples: &[f32], interval: f32, window: Window) -> Self {
+        let len = samples.len();
+        let n = len.next_power_of_two().max(2);
+        let mean = samples.iter().sum::<f32>() / len.max(1) as f32;
+
+        let mut re = vec![0.0; n];
+        let mut im = vec![0.0; n];
+        let mut weight_sq = 0.0;
+        for (i, sample) in samples.iter().enumerate() {
+            let weight = window.weight(i, len);
+            re[i] = weight * (sample - mean);
+            weight_sq += weight * weight;
+        }
+        fft(&mut re, &mut im);
+
+        let scale = if weight_sq > 0.0 {
+            interval / weight_sq
+        } else {
+            0.0
+        };
+        let power = (0..=n / 2)
+            .map(|k| {
+                // Negative frequencies fold onto positive ones, except at zero and Nyquist.
+                let fold = if k == 0 || k == n / 2 { 1.0 } else { 2.0 };
+                fold * scale * (re[k] * re[k] + im[k] * im[k])
+            })
+            .collect();
+
+        Self {
+            resolution: 1.0 / (n as f32 * interval),
+            power,
+        }
+    }
+
+    pub fn frequency(&self, bin: usize) -> f32 {
+        bin as f32 * self.resolution
+    }
+
+    /// Frequency and power of the strongest bin above zero frequency.
+    pub fn peak(&self) -> Option<(f32, f32)> {
+        self.power
+            .iter()
+            .enumerate()
+            .skip(1)
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(bin, &power)| (self.frequency(bin), power))
+    }
+}
+
+/// Frequencies of the two normal modes of small oscillations about hanging at rest, in hertz,
+/// slower first. `None` unless `model` has exactly two links.
+pub fn normal_mode_frequencies(model: &NPendulum) -> Option<[f32; 2]> {
+    if model.links() != 2 {
+        return None;
+    }
+
+    // Solve det(K - λM) = 0 with M the mass matrix at rest and K the potential's Hessian, which
+    // is diagonal with K_ii = g M_ii / l_i.
+    let m = model.mass_matrix(&[0.0_f32, 0.0]);
+    let k: Vec<f32> = (0..2)
+        .map(|i| model.gravity * m[i][i] / model.pendula[i].arm_length)
+        .collect();
+    let a = m[0][0] * m[1][1] - m[0][1] * m[1][0];
+    let b = -(k[0] * m[1][1] + k[1] * m[0][0]);
+    let c = k[0] * k[1];
+    let root = (b * b - 4.0 * a * c).max(0.0).sqrt();
+
+    let frequency = |lambda: f32| lambda.max(0.0).sqrt() / std::f32::consts::TAU;
+    Some([
+        frequency((-b - root) / (2.0 * a)),
+        frequency((-b + root) / (2.0 * a)),
+    ])
+}
+
+/// In-place radix-2 fast Fourier transform. The length must be a power of two.
+fn fft(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+    debug_assert!(n.is_power_of_two() && im.len() == n);
+
+    // Reorder into bit-reversed index order.
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let half = len / 2;
+        for start in (0..n).step_by(len) {
+            for k in 0..half {
+                let (sin, cos) = (-TAU * k as f64 / len as f64).sin_cos();
+                let (sin, cos) = (sin as f32, cos as f32);
+                let (a, b) = (start + k, start + k + half);
+                let t_re = re[b] * cos - im[b] * sin;
+                let t_im = re[b] * sin + im[b] * cos;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn peak_is_at_the_frequency_of_a_sine() {
+        let interval = 0.01;
+        let samples: Vec<f32> = (0..1000)
+            .map(|i| (TAU * 3.0 * i as f64 * interval as f64).sin() as f32 + 0.5)
+            .collect();
+
+        for window in Window::ALL {
+            let spectrum = Spectrum::new(&samples, interval, window);
+            let (frequency, _) = spectrum.peak().unwrap();
+            assert!(
+                (frequency - 3.0).abs() <= spectrum.resolution,
+                "{} window peaks at {frequency} Hz",
+                window.name()
+            );
+        }
+    }
+
+    #[test]
+    fn total_power_is_the_variance() {
+        // Deterministic noise from a linear congruential generator.
+        let mut seed = 12345_u32;
+        let samples: Vec<f32> = (0..1000)
+            .map(|_| {
+                seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                seed as f32 / u32::MAX as f32
+            })
+            .collect();
+
+        let mean = samples.iter().sum::<f32>() / samples.len() as f32;
+        let variance =
+            samples.iter().map(|x| (x - mean).powi(2)).sum::<f32>() / samples.len() as f32;
+
+        let spectrum = Spectrum::new(&samples, 0.02, Window::Rectangular);
+        let power = spectrum.power.iter().sum::<f32>() * spectrum.resolution;
+        assert!(
+            (power - variance).abs() < 1e-3 * variance,
+            "total power {power}, variance {variance}"
+        );
+    }
+
+    #[test]
+    fn normal_modes_of_equal_arms() {
+        let model = NPendulum::new(2);
+        let (g, l) = (model.gravity, model.pendula[0].arm_length);
+        let frequency = |factor: f32| (factor * g / l).sqrt() / std::f32::consts::TAU;
+
+        let [slow, fast] = normal_mode_frequencies(&model).unwrap();
+        assert!((slow - frequency(2.0 - 2.0_f32.sqrt())).abs() < 1e-4);
+        assert!((fast - frequency(2.0 + 2.0_f32.sqrt())).abs() < 1e-4);
+        assert!(normal_mode_frequencies(&NPendulum::new(3)).is_none());
+    }
+}
@@ -0,0 +1,421 @@
+//! The spectrum tab: power spectra of the first two angles over a recent window, and a scrolling
+//! spectrogram of one of them.
+
+use std::collections::VecDeque;
+
+use crate::colormap::gradient;
+use double_pendulum::{
+    pendulum::NPendulum,
+    spectrum::{self, Spectrum, Window},
+};
+use eframe::egui;
+
+/// Simulated seconds between samples of the angles.
+const SAMPLE_INTERVAL: f64 = 0.02;
+/// Samples kept, enough for the longest spectrum window.
+const MAX_SAMPLES: usize = 15_000;
+/// Simulated seconds of the past the spectrogram shows.
+const SPECTROGRAM_SPAN: f64 = 120.0;
+/// Decibels below the loudest bin that the spectrogram colour scale covers.
+const DYNAMIC_RANGE: f32 = 60.0;
+
+/// Spectrum of one segment of the spectrogram.
+struct Column {
+    /// Index of the segment's first sample.
+    start: usize,
+    /// Power of each bin, in decibels.
+    power: Vec<f32>,
+}
+
+pub struct SpectrumView {
+    window: Window,
+    /// Samples in the power spectrum.
+    window_len: usize,
+    /// Highest frequency shown, in hertz.
+    max_frequency: f32,
+    /// Link whose angle the spectrogram shows.
+    spectrogram_link: usize,
+    /// Samples in each segment of the spectrogram.
+    segment_len: usize,
+
+    /// Unwrapped angles of the first two arms, every `SAMPLE_INTERVAL` seconds.
+    samples: VecDeque<[f32; 2]>,
+    /// Index of the oldest sample kept, counting from the first one recorded.
+    first_sample: usize,
+    /// Simulated time of the first sample recorded.
+    start_time: f64,
+    next_sample_time: f64,
+    /// Time and angles at the last step, which samples are interpolated from.
+    last: Option<(f64, [f32; 2])>,
+
+    columns: VecDeque<Column>,
+    texture: Option<egui::TextureHandle>,
+    /// Frequency bins in the texture.
+    texture_bins: usize,
+    texture_stale: bool,
+}
+
+impl Default for SpectrumView {
+    fn default() -> Self {
+        Self {
+            window: Window::Hann,
+            window_len: 1024,
+            max_frequency: 5.0,
+            spectrogram_link: 0,
+            segment_len: 256,
+
+            samples: VecDeque::new(),
+            first_sample: 0,
+            start_time: 0.0,
+            next_sample_time: 0.0,
+            last: None,
+
+            columns: VecDeque::new(),
+            texture: None,
+            texture_bins: 0,
+            texture_stale: true,
+        }
+    }
+}
+
+impl SpectrumView {
+    /// Samples the angles of `pendulum` at simulated time `time`, interpolating from the last
+    /// call so that samples stay evenly spaced whatever the time step.
+    pub fn record(&mut self, time: f64, pendulum: &NPendulum) {
+        let angle = |link: usize| {
+            pendulum
+                .pendula
+                .get(link)
+                .map_or(0.0, |p| p.unwrapped_angle())
+        };
+        let angles = [angle(0), angle(1)];
+
+        match self.last {
+            Some((last_time, last_angles)) if time > last_time => {
+                while self.next_sample_time <= time {
+                    let t = ((self.next_sample_time - last_time) / (time - last_time)) as f32;
+                    self.samples.push_back(std::array::from_fn(|i| {
+                        last_angles[i] + t * (angles[i] - last_angles[i])
+                    }));
+                    self.next_sample_time += SAMPLE_INTERVAL;
+                }
+            }
+            Some(_) => return,
+            None => {
+                self.clear();
+                self.samples.push_back(angles);
+                self.start_time = time;
+                self.next_sample_time = time + SAMPLE_INTERVAL;
+            }
+        }
+        self.last = Some((time, angles));
+
+        while self.samples.len() > MAX_SAMPLES {
+            self.samples.pop_front();
+            self.first_sample += 1;
+        }
+    }
+
+    /// Forgets all samples, for when the motion starts over.
+    pub fn clear(&mut self) {
+        self.samples.clear();
+        self.first_sample = 0;
+        self.last = None;
+        self.columns.clear();
+        self.texture_stale = true;
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui, model: &NPendulum) {
+        let links = model.links().min(2);
+        self.spectrogram_link = self.spectrogram_link.min(links - 1);
+        let seconds = |samples: usize| format!("{:.1} s", samples as f64 * SAMPLE_INTERVAL);
+
+        let previous = (self.window, self.spectrogram_link, self.segment_len);
+        ui.horizontal_wrapped(|ui| {
+            ui.label("Window:");
+            egui::ComboBox::from_id_source("spectrum_window")
+                .selected_text(self.window.name())
+                .show_ui(ui, |ui| {
+                    for window in Window::ALL {
+                        ui.selectable_value(&mut self.window, window, window.name());
+                    }
+                });
+            egui::ComboBox::from_id_source("spectrum_length")
+                .selected_text(seconds(self.window_len))
+                .show_ui(ui, |ui| {
+                    for len in [256, 512, 1024, 2048, 4096] {
+                        ui.selectable_value(&mut self.window_len, len, seconds(len));
+                    }
+                });
+            ui.label("Up to");
+            ui.add(
+                egui::Slider::new(&mut self.max_frequency, 0.5..=25.0)
+                    .logarithmic(true)
+                    .suffix(" Hz"),
+            );
+            ui.separator();
+            ui.label("Spectrogram of");
+            egui::ComboBox::from_id_source("spectrogram_link")
+                .selected_text(format!("θ{}", self.spectrogram_link + 1))
+                .show_ui(ui, |ui| {
+                    for link in 0..links {
+                        ui.selectable_value(
+                            &mut self.spectrogram_link,
+                            link,
+                            format!("θ{}", link + 1),
+                        );
+                    }
+                });
+            ui.label("in segments of");
+            egui::ComboBox::from_id_source("spectrogram_segment")
+                .selected_text(seconds(self.segment_len))
+                .show_ui(ui, |ui| {
+                    for len in [128, 256, 512, 1024] {
+                        ui.selectable_value(&mut self.segment_len, len, seconds(len));
+                    }
+                });
+        });
+        if (self.window, self.spectrogram_link, self.segment_len) != previous {
+            self.columns.clear();
+            self.texture_stale = true;
+        }
+
+        let height = 0.5 * ui.available_height();
+        self.spectrum_plot(ui, model, links, height);
+        self.spectrogram_plot(ui);
+    }
+
+    fn spectrum_plot(&self, ui: &mut egui::Ui, model: &NPendulum, links: usize, height: f32) {
+        let len = self.samples.len().min(self.window_len);
+        if len < self.window_len {
+            ui.label(format!(
+                "Filling the window: {:.0} of {:.0} s simulated",
+                len as f64 * SAMPLE_INTERVAL,
+                self.window_len as f64 * SAMPLE_INTERVAL
+            ));
+        }
+
+        let spectra: Vec<Spectrum> = (0..links)
+            .map(|link| {
+                let samples: Vec<f32> = self
+                    .samples
+                    .range(self.samples.len() - len..)
+                    .map(|angles| angles[link])
+                    .collect();
+                Spectrum::new(&samples, SAMPLE_INTERVAL as f32, self.window)
+            })
+            .collect();
+        let normal_modes = spectrum::normal_mode_frequencies(model);
+
+        egui_plot::Plot::new("spectrum")
+            .height(height)
+            .allow_zoom(true)
+            .allow_scroll(false)
+            .allow_drag(false)
+            .include_x(0.0)
+            .include_x(self.max_frequency)
+            .x_axis_label("Frequency (Hz)")
+            .y_axis_label("Power (dB rad²/Hz)")
+            .legend(egui_plot::Legend::default())
+            .show(ui, |plot_ui| {
+                if len < 2 {
+                    return;
+                }
+                for (link, spectrum) in spectra.iter().enumerate() {
+                    let points: egui_plot::PlotPoints = spectrum
+                        .power
+                        .iter()
+                        .enumerate()
+                        .map(|(bin, &power)| (spectrum.frequency(bin), power))
+                        .take_while(|&(frequency, _)| frequency <= self.max_frequency)
+                        .map(|(frequency, power)| [frequency as f64, decibels(power) as f64])
+                        .collect();
+                    let name = match spectrum.peak() {
+                        Some((frequency, _)) => format!("θ{} (peak {frequency:.3} Hz)", link + 1),
+                        None => format!("θ{}", link + 1),
+                    };
+                    plot_ui.line(egui_plot::Line::new(points).name(name));
+                }
+                for frequency in normal_modes.into_iter().flatten() {
+                    plot_ui.vline(
+                        egui_plot::VLine::new(frequency)
+                            .color(egui::Color32::GRAY)
+                            .style(egui_plot::LineStyle::dashed_loose())
+                            .name("Normal modes at small amplitude"),
+                    );
+                }
+            });
+    }
+
+    fn spectrogram_plot(&mut self, ui: &mut egui::Ui) {
+        self.update_columns();
+        let (Some(first), Some(last)) = (self.columns.front(), self.columns.back()) else {
+            ui.label(format!(
+                "The spectrogram starts once {:.1} s are simulated.",
+                self.segment_len as f64 * SAMPLE_INTERVAL
+            ));
+            return;
+        };
+
+        let resolution = 1.0 / (self.segment_len as f64 * SAMPLE_INTERVAL);
+        let bins = ((self.max_frequency as f64 / resolution) as usize + 1).min(first.power.len());
+        let column_time = |column: &Column| {
+            self.start_time
+                + (column.start as f64 + 0.5 * self.segment_len as f64) * SAMPLE_INTERVAL
+        };
+        let hop = self.hop() as f64 * SAMPLE_INTERVAL;
+        let (start, end) = (
+            column_time(first) - 0.5 * hop,
+            column_time(last) + 0.5 * hop,
+        );
+
+        if std::mem::take(&mut self.texture_stale) || bins != self.texture_bins {
+            self.texture_bins = bins;
+            let image = self.image(bins);
+            match &mut self.texture {
+                Some(texture) => texture.set(image, egui::TextureOptions::LINEAR),
+                None => {
+                    self.texture = Some(ui.ctx().load_texture(
+                        "spectrogram",
+                        image,
+                        egui::TextureOptions::LINEAR,
+                    ))
+                }
+            }
+        }
+        let Some(texture) = &self.texture else {
+            return;
+        };
+
+        let top = bins as f64 * resolution;
+        egui_plot::Plot::new("spectrogram")
+            .allow_zoom(true)
+            .allow_scroll(false)
+            .allow_drag(false)
+            .include_y(0.0)
+            .include_y(top)
+            .x_axis_label("Time (s)")
+            .y_axis_label("Frequency (Hz)")
+            .show(ui, |plot_ui| {
+                plot_ui.image(egui_plot::PlotImage::new(
+                    texture,
+                    egui_plot::PlotPoint::new(0.5 * (start + end), 0.5 * top),
+                    egui::vec2((end - start) as f32, top as f32),
+                ));
+            });
+    }
+
+    /// Spacing of the spectrogram's segments, in samples.
+    fn hop(&self) -> usize {
+        self.segment_len / 8
+    }
+
+    /// Drops segments that scrolled out of view and transforms any completed since the last call.
+    fn update_columns(&mut self) {
+        let hop = self.hop();
+        let end = self.first_sample + self.samples.len();
+        let span = (SPECTROGRAM_SPAN / SAMPLE_INTERVAL) as usize;
+        let earliest = end
+            .saturating_sub(span)
+            .max(self.first_sample)
+            .div_ceil(hop)
+            * hop;
+
+        // Samples keep coming while the tab is hidden, so more than the span may have gone by.
+        while self
+            .columns
+            .front()
+            .is_some_and(|column| column.start < earliest)
+        {
+            self.columns.pop_front();
+            self.texture_stale = true;
+        }
+
+        let mut start = self
+            .columns
+            .back()
+            .map_or(earliest, |column| column.start + hop);
+        while start + self.segment_len <= end {
+            let samples: Vec<f32> = self
+                .samples
+                .range(start - self.first_sample..start - self.first_sample + self.segment_len)
+                .map(|angles| angles[self.spectrogram_link])
+                .collect();
+            let spectrum = Spectrum::new(&samples, SAMPLE_INTERVAL as f32, self.window);
+            self.columns.push_back(Column {
+                start,
+                power: spectrum.power.into_iter().map(decibels).collect(),
+            });
+            self.texture_stale = true;
+            start += hop;
+        }
+    }
+
+    /// The columns as an image, loudest in yellow, with the `bins` lowest frequencies from the
+    /// bottom up.
+    fn image(&self, bins: usize) -> egui::ColorImage {
+        let loudest = self
+            .columns
+            .iter()
+            .flat_map(|column| &column.power[..bins])
+            .copied()
+            .fold(f32::NEG_INFINITY, f32::max);
+
+        let width = self.columns.len();
+        let mut pixels = vec![egui::Color32::BLACK; width * bins];
+        for (x, column) in self.columns.iter().enumerate() {
+            for (bin, &power) in column.power[..bins].iter().enumerate() {
+                pixels[(bins - 1 - bin) * width + x] = gradient((loudest - power) / DYNAMIC_RANGE);
+            }
+        }
+        egui::ColorImage {
+            size: [width, bins],
+            pixels,
+        }
+    }
+}
+
+fn decibels(power: f32) -> f32 {
+    10.0 * power.max(1e-12).log10()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn columns_survive_a_long_hidden_stretch() {
+        let mut view = SpectrumView::default();
+        let mut pendulum = NPendulum::new(2);
+        pendulum.set_state(&double_pendulum::pendulum::State {
+            angles: vec![0.5, 0.5],
+            velocities: vec![0.0, 0.0],
+        });
+
+        let mut time = 0.0;
+        let mut run = |view: &mut SpectrumView, duration: f64| {
+            while time < duration {
+                time += 0.1;
+                pendulum.update(0.1).unwrap();
+                view.record(time, &pendulum);
+            }
+        };
+
+        run(&mut view, 60.0);
+        view.update_columns();
+        assert!(!view.columns.is_empty());
+
+        // Longer than the samples kept, as if the tab had been hidden all along.
+        run(&mut view, 60.0 + 2.0 * MAX_SAMPLES as f64 * SAMPLE_INTERVAL);
+        view.update_columns();
+
+        let first = view.columns.front().unwrap().start;
+        assert!(first >= view.first_sample);
+        let starts: Vec<usize> = view.columns.iter().map(|column| column.start).collect();
+        assert!(starts
+            .windows(2)
+            .all(|pair| pair[1] == pair[0] + view.hop()));
+        let end = view.first_sample + view.samples.len();
+        assert!(starts.last().unwrap() + view.segment_len <= end);
+    }
+}
@@ -0,0 +1,270 @@
+use crate::{pendulum::NPendulum, trajectory::round_time};
+use serde::Serialize;
+use std::fmt::Write;
+
+/// A model parameter that a parameter sweep can vary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Parameter {
+    /// Mass of every lower bob relative to the first one.
+    MassRatio,
+    /// Length of every lower arm relative to the first one.
+    LengthRatio,
+    Gravity,
+    /// Initial angle of the first arm, in radians.
+    Angle1,
+    /// Initial angle of the second arm, in radians.
+    Angle2,
+    /// Joint friction of every joint.
+    Damping,
+}
+
+impl Parameter {
+    pub const ALL: [Parameter; 6] = [
+        Parameter::MassRatio,
+        Parameter::LengthRatio,
+        Parameter::Gravity,
+        Parameter::Angle1,
+        Parameter::Angle2,
+        Parameter::Damping,
+    ];
+
+    /// Identifier of the parameter on the command line and in result tables.
+    pub fn key(&self) -> &'static str {
+        match self {
+            Parameter::MassRatio => "mass_ratio",
+            Parameter::LengthRatio => "length_ratio",
+            Parameter::Gravity => "gravity",
+            Parameter::Angle1 => "angle1",
+            Parameter::Angle2 => "angle2",
+            Parameter::Damping => "damping",
+        }
+    }
+
+    /// Fewest links a pendulum needs for the parameter to mean anything.
+    pub fn min_links(&self) -> usize {
+        match self {
+            Parameter::MassRatio | Parameter::LengthRatio | Parameter::Angle2 => 2,
+            Parameter::Gravity | Parameter::Angle1 | Parameter::Damping => 1,
+        }
+    }
+
+    /// Sets the parameter of `pendulum` to `value`, keeping it at its current state otherwise.
+    pub fn apply(&self, pendulum: &mut NPendulum, value: f32) {
+        let mut state = pendulum.state();
+        let (first, rest) = pendulum
+            .pendula
+            .split_first_mut()
+            .expect("a pendulum has at least one link");
+
+        match self {
+            Parameter::MassRatio => rest.iter_mut().for_each(|p| p.mass = value * first.mass),
+            Parameter::LengthRatio => rest
+                .iter_mut()
+                .for_each(|p| p.arm_length = value * first.arm_length),
+            Parameter::Gravity => pendulum.gravity = value,
+            Parameter::Angle1 => state.angles[0] = value,
+            Parameter::Angle2 => state.angles[1] = value,
+            Parameter::Damping => pendulum
+                .pendula
+                .iter_mut()
+                .for_each(|p| p.joint_damping = value),
+        }
+
+        pendulum.set_state(&state);
+    }
+}
+
+impl std::str::FromStr for Parameter {
+    type Err = String;
+
+    fn from_str(key: &str) -> Result<Self, Self::Err> {
+        Parameter::ALL
+            .into_iter()
+            .find(|parameter| parameter.key() == key)
+            .ok_or_else(|| {
+                let keys: Vec<_> = Parameter::ALL.iter().map(Parameter::key).collect();
+                format!(
+                    "unknown parameter {key:?}; expected one of {}",
+                    keys.join(", ")
+                )
+            })
+    }
+}
+
+/// Evenly spaced values of a parameter, both ends included.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Axis {
+    pub parameter: Parameter,
+    pub start: f32,
+    pub end: f32,
+    pub count: usize,
+}
+
+impl Axis {
+    pub fn value(&self, index: usize) -> f32 {
+        if self.count < 2 {
+            return self.start;
+        }
+        let t = index as f32 / (self.count - 1) as f32;
+        self.start + t * (self.end - self.start)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = f32> + '_ {
+        (0..self.count).map(|index| self.value(index))
+    }
+}
+
+impl std::str::FromStr for Axis {
+    type Err = String;
+
+    /// Parses `parameter=start:end:count`, e.g. `mass_ratio=0.1:10:50`.
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("expected parameter=start:end:count, got {spec:?}");
+
+        let (parameter, range) = spec.split_once('=').ok_or_else(invalid)?;
+        let [start, end, count] = range
+            .split(':')
+            .collect::<Vec<_>>()
+            .try_into()
+            .map_err(|_| invalid())?;
+
+        let axis = Axis {
+            parameter: parameter.parse()?,
+            start: start.parse().map_err(|_| invalid())?,
+            end: end.parse().map_err(|_| invalid())?,
+            count: count.parse().map_err(|_| invalid())?,
+        };
+        if axis.count == 0 {
+            return Err(format!("{spec:?} has no values"));
+        }
+        Ok(axis)
+    }
+}
+
+/// Figures of merit of a single run.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Summary {
+    /// Flips of all arms together.
+    pub flips: u32,
+    /// Simulated time until any arm first flipped, if one did.
+    pub first_flip_time: Option<f64>,
+    /// Largest angular velocity of any arm, in rad/s.
+    pub max_angular_velocity: f32,
+    pub final_energy: f32,
+    /// Simulated time at which the run diverged, if it did; the other figures cover the run up
+    /// to then.
+    pub diverged_at: Option<f64>,
+}
+
+impl Summary {
+    /// Runs `pendulum` for `steps` steps of `time_step` seconds and summarises the run.
+    pub fn of_run(mut pendulum: NPendulum, time_step: f64, steps: u64) -> Self {
+        let max_velocity = |pendulum: &NPendulum| {
+            pendulum
+                .pendula
+                .iter()
+                .map(|p| p.velocity.abs())
+                .fold(0.0, f32::max)
+        };
+
+        let mut first_flip_time = None;
+        let mut diverged_at = None;
+        let mut max_angular_velocity = max_velocity(&pendulum);
+        for step in 1..=steps {
+            if pendulum.update(time_step as f32).is_err() {
+                diverged_at = Some(round_time(step as f64 * time_step));
+                break;
+            }
+            max_angular_velocity = max_angular_velocity.max(max_velocity(&pendulum));
+            if first_flip_time.is_none() && pendulum.pendula.iter().any(|p| p.flips > 0) {
+                first_flip_time = Some(round_time(step as f64 * time_step));
+            }
+        }
+
+        Self {
+            flips: pendulum.pendula.iter().map(|p| p.flips).sum(),
+            first_flip_time,
+            max_angular_velocity,
+            final_energy: pendulum.total_energy(),
+            diverged_at,
+        }
+    }
+
+    /// Column names matching [`Summary::csv_row`].
+    pub const CSV_HEADER: &'static str =
+        "flips,first_flip_time,max_angular_velocity,final_energy,diverged_at";
+
+    /// The summary as CSV cells; a run without flips leaves `first_flip_time` empty, and one
+    /// that did not diverge `diverged_at`.
+    pub fn csv_row(&self) -> String {
+        let cell = |time: Option<f64>| time.map(|time| time.to_string()).unwrap_or_default();
+        let mut row = self.flips.to_string();
+        write!(
+            row,
+            ",{},{},{},{}",
+            cell(self.first_flip_time),
+            self.max_angular_velocity,
+            self.final_energy,
+            cell(self.diverged_at)
+        )
+        .ok();
+        row
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(spec: &str) -> Vec<f32> {
+        spec.parse::<Axis>().unwrap().values().collect()
+    }
+
+    #[test]
+    fn parses_an_axis() {
+        let axis: Axis = "mass_ratio=0.5:2:4".parse().unwrap();
+        assert_eq!(
+            axis,
+            Axis {
+                parameter: Parameter::MassRatio,
+                start: 0.5,
+                end: 2.0,
+                count: 4,
+            }
+        );
+        assert_eq!(values("mass_ratio=0.5:2:4"), [0.5, 1.0, 1.5, 2.0]);
+    }
+
+    #[test]
+    fn reversed_ranges_count_down() {
+        assert_eq!(values("gravity=2:0.5:4"), [2.0, 1.5, 1.0, 0.5]);
+    }
+
+    #[test]
+    fn single_value_axis_is_its_start() {
+        assert_eq!(values("angle1=0.25:3:1"), [0.25]);
+        assert!("angle1=0.25:3:0".parse::<Axis>().is_err());
+    }
+
+    #[test]
+    fn rejects_malformed_axes() {
+        for spec in [
+            "",
+            "mass_ratio",
+            "mass_ratio=0.5:2",
+            "mass_ratio=0.5:2:4:8",
+            "mass_ratio=a:2:4",
+            "mass_ratio=0.5:2:-4",
+            "mass_ratio=0.5:2:1.5",
+        ] {
+            assert!(spec.parse::<Axis>().is_err(), "accepted {spec:?}");
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_parameters() {
+        let error = "mass=0.5:2:4".parse::<Axis>().unwrap_err();
+        assert!(error.contains("\"mass\""), "{error}");
+        assert!("Mass_Ratio=0.5:2:4".parse::<Axis>().is_err());
+    }
+}
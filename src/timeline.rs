@@ -0,0 +1,127 @@
+use crate::pendulum::NPendulum;
+use std::collections::VecDeque;
+
+/// Snapshots of a pendulum over a run, for going back to an earlier moment.
+///
+/// Snapshots are keyed by simulated time, in increasing order. Recording at a time before the
+/// latest snapshot forks the run: every snapshot from that time on is dropped first.
+#[derive(Debug, Clone)]
+pub struct Timeline {
+    capacity: usize,
+    snapshots: VecDeque<(f64, NPendulum)>,
+}
+
+impl Timeline {
+    /// Keeps at most `capacity` snapshots, dropping the oldest ones first.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            snapshots: VecDeque::new(),
+        }
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+    }
+
+    /// Records `pendulum` as it is at simulated time `time`, replacing any snapshots at or after
+    /// that time.
+    pub fn record(&mut self, time: f64, pendulum: &NPendulum) {
+        let kept = self.snapshots.partition_point(|(t, _)| *t < time);
+        self.snapshots.truncate(kept);
+        self.snapshots.push_back((time, pendulum.clone()));
+
+        while self.snapshots.len() > self.capacity {
+            self.snapshots.pop_front();
+        }
+    }
+
+    /// Time of the earliest snapshot kept.
+    pub fn start(&self) -> Option<f64> {
+        self.snapshots.front().map(|(time, _)| *time)
+    }
+
+    /// Time of the latest snapshot.
+    pub fn end(&self) -> Option<f64> {
+        self.snapshots.back().map(|(time, _)| *time)
+    }
+
+    /// The latest snapshot at or before `time`, or the earliest one if `time` is before them all.
+    pub fn at(&self, time: f64) -> Option<(f64, &NPendulum)> {
+        let index = self.snapshots.partition_point(|(t, _)| *t <= time);
+        self.get(index.saturating_sub(1))
+    }
+
+    /// The latest snapshot strictly before `time`.
+    pub fn before(&self, time: f64) -> Option<(f64, &NPendulum)> {
+        let index = self.snapshots.partition_point(|(t, _)| *t < time);
+        self.get(index.checked_sub(1)?)
+    }
+
+    /// The earliest snapshot strictly after `time`.
+    pub fn after(&self, time: f64) -> Option<(f64, &NPendulum)> {
+        self.get(self.snapshots.partition_point(|(t, _)| *t <= time))
+    }
+
+    fn get(&self, index: usize) -> Option<(f64, &NPendulum)> {
+        self.snapshots
+            .get(index)
+            .map(|(time, pendulum)| (*time, pendulum))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A one-link pendulum tagged with `angle`, to tell snapshots apart.
+    fn pendulum(angle: f32) -> NPendulum {
+        let mut pendulum = NPendulum::new(1);
+        pendulum.pendula[0].angle = angle;
+        pendulum
+    }
+
+    fn angle_at(timeline: &Timeline, time: f64) -> Option<f32> {
+        timeline
+            .at(time)
+            .map(|(_, pendulum)| pendulum.pendula[0].angle)
+    }
+
+    #[test]
+    fn recording_in_the_past_forks_the_run() {
+        let mut timeline = Timeline::new(10);
+        for i in 0..5 {
+            timeline.record(i as f64, &pendulum(i as f32));
+        }
+
+        // Rewind to 2 s, edit, and carry on from there.
+        timeline.record(2.0, &pendulum(-2.0));
+        assert_eq!(timeline.end(), Some(2.0));
+        assert_eq!(angle_at(&timeline, 2.0), Some(-2.0));
+        assert_eq!(angle_at(&timeline, 1.0), Some(1.0));
+        assert!(timeline.after(2.0).is_none());
+
+        timeline.record(2.5, &pendulum(-2.5));
+        assert_eq!(timeline.end(), Some(2.5));
+        assert_eq!(timeline.before(2.5).map(|(time, _)| time), Some(2.0));
+    }
+
+    #[test]
+    fn oldest_snapshots_are_evicted_beyond_capacity() {
+        let mut timeline = Timeline::new(3);
+        for i in 0..5 {
+            timeline.record(i as f64, &pendulum(i as f32));
+        }
+
+        assert_eq!(timeline.start(), Some(2.0));
+        assert_eq!(timeline.end(), Some(4.0));
+        assert!(timeline.before(2.0).is_none());
+        // Asking for a time before the earliest snapshot gives the earliest one.
+        assert_eq!(angle_at(&timeline, 0.0), Some(2.0));
+    }
+}
@@ -0,0 +1,70 @@
+use crate::pendulum::NPendulum;
+use serde::Serialize;
+use std::fmt::Write;
+
+/// Snapshot of an [`NPendulum`] at one instant, for writing trajectories out.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Sample {
+    /// Simulated seconds since the start of the run.
+    pub time: f64,
+    /// Unwrapped angles in radians, so whole turns show up as multiples of 2π.
+    pub angles: Vec<f32>,
+    pub velocities: Vec<f32>,
+    pub accelerations: Vec<f32>,
+    /// Bob positions in metres, relative to the fixed pivot with y pointing down.
+    pub positions: Vec<[f32; 2]>,
+    pub flips: Vec<u32>,
+    pub kinetic_energy: f32,
+    pub potential_energy: f32,
+}
+
+impl Sample {
+    pub fn new(time: f64, pendulum: &NPendulum) -> Self {
+        let pendula = &pendulum.pendula;
+        Self {
+            time: round_time(time),
+            angles: pendula.iter().map(|p| p.unwrapped_angle()).collect(),
+            velocities: pendula.iter().map(|p| p.velocity).collect(),
+            accelerations: pendula.iter().map(|p| p.acceleration).collect(),
+            positions: pendula.iter().map(|p| p.position()).collect(),
+            flips: pendula.iter().map(|p| p.flips).collect(),
+            kinetic_energy: pendulum.kinetic_energy(),
+            potential_energy: pendulum.potential_energy(),
+        }
+    }
+
+    /// Column names matching [`Sample::csv_row`] for a chain of `links` arms.
+    pub fn csv_header(links: usize) -> String {
+        let mut header = String::from("time");
+        for link in 1..=links {
+            write!(
+                header,
+                ",angle_{link},velocity_{link},acceleration_{link},x_{link},y_{link},flips_{link}"
+            )
+            .ok();
+        }
+        header.push_str(",kinetic_energy,potential_energy");
+        header
+    }
+
+    pub fn csv_row(&self) -> String {
+        let mut row = self.time.to_string();
+        for i in 0..self.angles.len() {
+            let [x, y] = self.positions[i];
+            write!(
+                row,
+                ",{},{},{},{x},{y},{}",
+                self.angles[i], self.velocities[i], self.accelerations[i], self.flips[i]
+            )
+            .ok();
+        }
+        write!(row, ",{},{}", self.kinetic_energy, self.potential_energy).ok();
+        row
+    }
+}
+
+/// Rounds a simulated time to whole nanoseconds, so that times summed from decimal time steps
+/// read as such, e.g. `0.3` rather than `0.30000000000000004`.
+pub fn round_time(time: f64) -> f64 {
+    (time * 1e9).round() / 1e9
+}